@@ -2,32 +2,63 @@
 // Unexpected arch "spirv"
 #![allow(unexpected_cfgs)]
 
-use shared::{UniformBufferObject, VertexData};
-use spirv_std::glam::{Vec2, Vec3, Vec4};
-use spirv_std::{spirv, Image};
+use shared::{
+    CullObject, CullPushConstants, DrawIndexedIndirectCommand, InstanceData, ObjectData,
+    OutputPushConstants, PushConstants, UniformBufferObject, VertexData, INDIRECT_OBJECT_INDEX,
+    OUTPUT_TRANSFORM_PQ, OUTPUT_TRANSFORM_SCRGB, OUTPUT_TRANSFORM_SRGB,
+};
+use spirv_std::arch::atomic_i_add;
+use spirv_std::glam::{Mat3, Mat4, UVec3, Vec2, Vec3, Vec4};
+use spirv_std::memory::{Scope, Semantics};
+use spirv_std::{spirv, Image, RuntimeArray, Sampler};
 use spirv_std::num_traits::Float;
 
 #[spirv(vertex)]
 pub fn main_vs(
     in_data: VertexData,
+    in_instance: InstanceData,
     #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UniformBufferObject,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] objects: &[ObjectData],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] draw_object_indices: &[u32],
+    #[spirv(push_constant)] push_constants: &PushConstants,
+    #[spirv(draw_index)] draw_index: u32,
     #[spirv(position)] out_pos: &mut Vec4,
     out_frag_colour: &mut Vec3,
     out_frag_tex_coord: &mut Vec2,
+    #[spirv(flat)] out_texture_index: &mut u32,
+    #[spirv(flat)] out_sampler_index: &mut u32,
 ) {
     let position = in_data.position.extend(0.0).extend(1.0);
 
-    *out_pos = ubo.projection * ubo.view * ubo.model * position;
-    *out_frag_colour = in_data.colour;
+    let instance_model = Mat4::from_cols(
+        in_instance.model_x,
+        in_instance.model_y,
+        in_instance.model_z,
+        in_instance.model_w,
+    );
+    let object_index = if push_constants.object_index == INDIRECT_OBJECT_INDEX {
+        draw_object_indices[draw_index as usize]
+    } else {
+        push_constants.object_index
+    };
+    let object = &objects[object_index as usize];
+    let model = object.model * instance_model;
+
+    *out_pos = ubo.projection * ubo.view * model * position;
+    *out_frag_colour = in_data.colour * in_instance.colour.truncate();
     *out_frag_tex_coord = in_data.tex_coord;
+    *out_texture_index = object.texture_index;
+    *out_sampler_index = object.sampler_index;
 }
 
 #[spirv(fragment)]
 pub fn main_fs(
     frag_colour: Vec3,
     frag_tex_coord: Vec2,
-    #[spirv(descriptor_set = 0, binding = 1)] image: &Image![2D, format = rgba8, sampled],
-    #[spirv(descriptor_set = 0, binding = 1)] sampler: &spirv_std::Sampler,
+    #[spirv(flat)] texture_index: u32,
+    #[spirv(flat)] sampler_index: u32,
+    #[spirv(descriptor_set = 1, binding = 0)] images: &RuntimeArray<Image![2D, type = f32, sampled]>,
+    #[spirv(descriptor_set = 1, binding = 1)] samplers: &RuntimeArray<Sampler>,
     #[spirv(frag_coord)] point_coord: Vec4,
     output: &mut Vec4,
 ) {
@@ -35,8 +66,115 @@ pub fn main_fs(
     let y = point_coord.y as u32;
     
     if x % 6 == 1 || y % 4 == 1 && (x + 2 * y) % 5 < 2 {
+        // Safety: the indices are the object's, whose texture was written to both arrays. They're
+        //  the same for the whole draw, so dynamically uniform
+        let image = unsafe { images.index(texture_index as usize) };
+        let sampler = unsafe { samplers.index(sampler_index as usize) };
         *output = image.sample(*sampler, frag_tex_coord);
     } else {
         *output = frag_colour.extend(1.0);
     }
 }
+
+/// Frustum cull every object, writing a compacted list of draws for the visible ones
+#[spirv(compute(threads(64)))]
+pub fn cull_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(push_constant)] params: &CullPushConstants,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] objects: &[CullObject],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] draw_commands: &mut [DrawIndexedIndirectCommand],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] draw_count: &mut u32,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] draw_object_indices: &mut [u32],
+) {
+    let index = id.x as usize;
+    if index >= params.object_count as usize {
+        return;
+    }
+
+    let object = &objects[index];
+    let centre = object.bounding_sphere.truncate();
+    let radius = object.bounding_sphere.w;
+
+    let mut i = 0;
+    while i < 6 {
+        let plane = params.frustum_planes[i];
+        if plane.truncate().dot(centre) + plane.w < -radius {
+            return;
+        }
+        i += 1;
+    }
+
+    let slot = unsafe {
+        atomic_i_add::<u32, { Scope::Device as u32 }, { Semantics::NONE.bits() }>(draw_count, 1)
+    } as usize;
+    draw_commands[slot] = object.draw;
+    draw_object_indices[slot] = object.object_index;
+}
+
+/// A single triangle covering the whole screen, with UVs covering `0..1` over the screen
+#[spirv(vertex)]
+pub fn output_vs(
+    #[spirv(vertex_index)] vertex_index: i32,
+    #[spirv(position)] out_pos: &mut Vec4,
+    out_uv: &mut Vec2,
+) {
+    let uv = Vec2::new(((vertex_index << 1) & 2) as f32, (vertex_index & 2) as f32);
+    *out_pos = (uv * 2.0 - Vec2::ONE).extend(0.0).extend(1.0);
+    *out_uv = uv;
+}
+
+/// Convert the linear scene colour into the swapchain's colour space
+#[spirv(fragment)]
+pub fn output_fs(
+    uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] image: &Image![2D, format = rgba16f, sampled],
+    #[spirv(descriptor_set = 0, binding = 0)] sampler: &spirv_std::Sampler,
+    #[spirv(push_constant)] params: &OutputPushConstants,
+    output: &mut Vec4,
+) {
+    let colour: Vec4 = image.sample(*sampler, uv);
+    let colour = colour.truncate().max(Vec3::ZERO);
+
+    let encoded = if params.transform == OUTPUT_TRANSFORM_PQ {
+        let nits = rec709_to_rec2020() * colour * params.paper_white_nits;
+        Vec3::new(pq_oetf(nits.x), pq_oetf(nits.y), pq_oetf(nits.z))
+    } else if params.transform == OUTPUT_TRANSFORM_SCRGB {
+        colour * (params.paper_white_nits / 80.0)
+    } else if params.transform == OUTPUT_TRANSFORM_SRGB {
+        let colour = colour.min(Vec3::ONE);
+        Vec3::new(srgb_oetf(colour.x), srgb_oetf(colour.y), srgb_oetf(colour.z))
+    } else {
+        colour.min(Vec3::ONE)
+    };
+
+    *output = encoded.extend(1.0);
+}
+
+fn rec709_to_rec2020() -> Mat3 {
+    Mat3::from_cols(
+        Vec3::new(0.6274, 0.0691, 0.0164),
+        Vec3::new(0.3293, 0.9195, 0.0880),
+        Vec3::new(0.0433, 0.0114, 0.8956),
+    )
+}
+
+/// sRGB transfer function, from linear `0..1`
+fn srgb_oetf(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// SMPTE ST 2084 (PQ) inverse EOTF, from absolute luminance in nits
+fn pq_oetf(nits: f32) -> f32 {
+    const M1: f32 = 0.1593017578125;
+    const M2: f32 = 78.84375;
+    const C1: f32 = 0.8359375;
+    const C2: f32 = 18.8515625;
+    const C3: f32 = 18.6875;
+
+    let y = (nits / 10000.0).clamp(0.0, 1.0).powf(M1);
+    ((C1 + C2 * y) / (1.0 + C3 * y)).powf(M2)
+}
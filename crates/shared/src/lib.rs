@@ -12,9 +12,92 @@ pub struct VertexData {
     pub tex_coord: glam::Vec2,
 }
 
+/// Per-instance vertex data, read through a vertex binding with an instance input rate
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct InstanceData {
+    /// Columns of the instance's transform, applied before its object's transform
+    pub model_x: glam::Vec4,
+    pub model_y: glam::Vec4,
+    pub model_z: glam::Vec4,
+    pub model_w: glam::Vec4,
+    /// Multiplied with the vertex colour
+    pub colour: glam::Vec4,
+}
+
 #[repr(C)]
 pub struct UniformBufferObject {
-    pub model: glam::Mat4,
     pub view: glam::Mat4,
     pub projection: glam::Mat4,
 }
+
+/// Per-draw data, pushed with `vkCmdPushConstants` rather than going through a buffer
+#[repr(C)]
+pub struct PushConstants {
+    /// Index into the `ObjectData` storage buffer, or `INDIRECT_OBJECT_INDEX`
+    pub object_index: u32,
+}
+
+/// `PushConstants::object_index` for indirect draws, where each draw's object index is looked up
+/// from the draw object indices written by `cull_cs` instead
+pub const INDIRECT_OBJECT_INDEX: u32 = u32::MAX;
+
+/// Per-object data, one entry per drawn object in a storage buffer
+#[repr(C)]
+pub struct ObjectData {
+    pub model: glam::Mat4,
+    /// Index into the bindless image array of the object's texture
+    pub texture_index: u32,
+    /// Index into the bindless sampler array to sample the texture with
+    pub sampler_index: u32,
+    /// Keeps the size a multiple of the `Mat4` alignment on both the CPU and GPU
+    pub _padding: [u32; 2],
+}
+
+/// Matches the layout of `VkDrawIndexedIndirectCommand`
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct DrawIndexedIndirectCommand {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    pub first_instance: u32,
+}
+
+/// An object for the culling shader to test, and the draw to emit if it's visible
+#[repr(C)]
+pub struct CullObject {
+    /// World space bounding sphere, with the radius in `w`
+    pub bounding_sphere: glam::Vec4,
+    pub draw: DrawIndexedIndirectCommand,
+    /// Index into the `ObjectData` storage buffer
+    pub object_index: u32,
+}
+
+#[repr(C)]
+pub struct CullPushConstants {
+    /// Inward facing planes, as `(normal, distance)`
+    pub frustum_planes: [glam::Vec4; 6],
+    pub object_count: u32,
+    /// Pads the size to a multiple of the `Vec4` alignment, so no bytes are left uninitialised
+    pub _padding: [u32; 3],
+}
+
+/// `OutputPushConstants::transform`: the swapchain encodes to sRGB itself, so output linear
+pub const OUTPUT_TRANSFORM_LINEAR: u32 = 0;
+/// `OutputPushConstants::transform`: apply the sRGB transfer function, for UNORM SDR swapchains
+pub const OUTPUT_TRANSFORM_SRGB: u32 = 1;
+/// `OutputPushConstants::transform`: extended sRGB linear (scRGB), where 1.0 is 80 nits
+pub const OUTPUT_TRANSFORM_SCRGB: u32 = 2;
+/// `OutputPushConstants::transform`: HDR10, Rec.2020 primaries with the ST 2084 (PQ) curve
+pub const OUTPUT_TRANSFORM_PQ: u32 = 3;
+
+/// Parameters for the final pass, which converts the linear scene colour for the swapchain
+#[repr(C)]
+pub struct OutputPushConstants {
+    /// One of the `OUTPUT_TRANSFORM_*` constants
+    pub transform: u32,
+    /// Brightness in nits that a scene value of 1.0 is shown at on HDR outputs
+    pub paper_white_nits: f32,
+}
@@ -1,8 +1,15 @@
-use spirv_builder::{MetadataPrintout, SpirvBuilder};
+use spirv_builder::{Capability, MetadataPrintout, SpirvBuilder};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     SpirvBuilder::new("../../crates/shaders", "spirv-unknown-vulkan1.2")
         .print_metadata(MetadataPrintout::Full)
+        // For `draw_index` in the vertex shader
+        .capability(Capability::DrawParameters)
+        // For the bindless texture arrays sampled in the fragment shader
+        .capability(Capability::RuntimeDescriptorArray)
+        .extension("SPV_EXT_descriptor_indexing")
+        // To call `debug_printf!` from a shader, uncomment this and set `[validation] debug_printf`
+        // .extension("SPV_KHR_non_semantic_info")
         .build()?;
     Ok(())
 }
\ No newline at end of file
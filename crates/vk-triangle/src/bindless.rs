@@ -0,0 +1,180 @@
+use crate::owned::{self, Owned};
+use crate::result::{error, Result};
+use std::slice;
+
+/// Length of the sampled image array. Well within the 500000 `maxDescriptorSetUpdateAfterBind*`
+///  minimum of devices supporting descriptor indexing
+pub(crate) const MAX_BINDLESS_IMAGES: u32 = 1024;
+/// Length of the sampler array
+pub(crate) const MAX_BINDLESS_SAMPLERS: u32 = 16;
+
+/// A texture in the bindless arrays, as indices into the image and sampler arrays
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BindlessTexture {
+    pub image: u32,
+    pub sampler: u32,
+}
+
+/// One descriptor set holding every loaded texture, bound once at set 1 so shaders can sample any
+/// of them by index.
+///
+/// Both arrays are partially bound, so only the elements written so far can be used, and
+/// update-after-bind, so textures can be added while frames using the set are in flight.
+pub(crate) struct BindlessTextures {
+    /// Freeing the pool frees `descriptor_set`
+    pub descriptor_pool: Owned<ash::vk::DescriptorPool>,
+    pub descriptor_set: ash::vk::DescriptorSet,
+    pub descriptor_set_layout: Owned<ash::vk::DescriptorSetLayout>,
+    image_count: u32,
+    sampler_count: u32,
+}
+
+impl BindlessTextures {
+    /// Whether the device has the Vulkan 1.2 descriptor indexing features this needs
+    pub fn supported(features12: &ash::vk::PhysicalDeviceVulkan12Features) -> bool {
+        features12.descriptor_indexing == ash::vk::TRUE
+            && features12.runtime_descriptor_array == ash::vk::TRUE
+            && features12.descriptor_binding_partially_bound == ash::vk::TRUE
+            && features12.descriptor_binding_sampled_image_update_after_bind == ash::vk::TRUE
+            && features12.descriptor_binding_update_unused_while_pending == ash::vk::TRUE
+    }
+
+    /// Enable the features checked by `supported`
+    pub fn enable_features(
+        features12: ash::vk::PhysicalDeviceVulkan12Features,
+    ) -> ash::vk::PhysicalDeviceVulkan12Features {
+        features12
+            .descriptor_indexing(true)
+            .runtime_descriptor_array(true)
+            .descriptor_binding_partially_bound(true)
+            .descriptor_binding_sampled_image_update_after_bind(true)
+            .descriptor_binding_update_unused_while_pending(true)
+    }
+
+    pub fn new(device: &owned::Device) -> Result<Self> {
+        let bindings = [
+            ash::vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(ash::vk::DescriptorType::SAMPLED_IMAGE)
+                .descriptor_count(MAX_BINDLESS_IMAGES)
+                .stage_flags(ash::vk::ShaderStageFlags::FRAGMENT),
+            ash::vk::DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(ash::vk::DescriptorType::SAMPLER)
+                .descriptor_count(MAX_BINDLESS_SAMPLERS)
+                .stage_flags(ash::vk::ShaderStageFlags::FRAGMENT),
+        ];
+        // Unused-while-pending lets new elements be written while frames using others are in flight
+        let flags = ash::vk::DescriptorBindingFlags::PARTIALLY_BOUND
+            | ash::vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | ash::vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING;
+        let binding_flags = [flags; 2];
+        let mut binding_flags_info = ash::vk::DescriptorSetLayoutBindingFlagsCreateInfo::default()
+            .binding_flags(&binding_flags);
+        let layout_info = ash::vk::DescriptorSetLayoutCreateInfo::default()
+            .flags(ash::vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+            .bindings(&bindings)
+            .push_next(&mut binding_flags_info);
+        let descriptor_set_layout = unsafe {
+            Owned::new(
+                device,
+                device.create_descriptor_set_layout(&layout_info, None)?,
+            )
+        };
+
+        let pool_sizes = [
+            ash::vk::DescriptorPoolSize::default()
+                .ty(ash::vk::DescriptorType::SAMPLED_IMAGE)
+                .descriptor_count(MAX_BINDLESS_IMAGES),
+            ash::vk::DescriptorPoolSize::default()
+                .ty(ash::vk::DescriptorType::SAMPLER)
+                .descriptor_count(MAX_BINDLESS_SAMPLERS),
+        ];
+        let pool_info = ash::vk::DescriptorPoolCreateInfo::default()
+            .flags(ash::vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+            .pool_sizes(&pool_sizes)
+            .max_sets(1);
+        let descriptor_pool =
+            unsafe { Owned::new(device, device.create_descriptor_pool(&pool_info, None)?) };
+
+        let alloc_info = ash::vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(*descriptor_pool)
+            .set_layouts(slice::from_ref(&*descriptor_set_layout));
+        let descriptor_set = unsafe { device.allocate_descriptor_sets(&alloc_info) }?[0];
+
+        Ok(Self {
+            descriptor_pool,
+            descriptor_set,
+            descriptor_set_layout,
+            image_count: 0,
+            sampler_count: 0,
+        })
+    }
+
+    /// Add `image_view` to the image array, returning its index. It must be in `layout` whenever
+    /// it's sampled
+    pub fn add_image(
+        &mut self,
+        device: &ash::Device,
+        image_view: ash::vk::ImageView,
+        layout: ash::vk::ImageLayout,
+    ) -> Result<u32> {
+        if self.image_count == MAX_BINDLESS_IMAGES {
+            return error(&format!(
+                "At most {MAX_BINDLESS_IMAGES} bindless images are supported"
+            ));
+        }
+
+        let image_info = ash::vk::DescriptorImageInfo::default()
+            .image_view(image_view)
+            .image_layout(layout);
+        let index = self.image_count;
+        self.write(
+            device,
+            0,
+            index,
+            ash::vk::DescriptorType::SAMPLED_IMAGE,
+            image_info,
+        );
+        self.image_count += 1;
+        Ok(index)
+    }
+
+    /// Add `sampler` to the sampler array, returning its index
+    pub fn add_sampler(&mut self, device: &ash::Device, sampler: ash::vk::Sampler) -> Result<u32> {
+        if self.sampler_count == MAX_BINDLESS_SAMPLERS {
+            return error(&format!(
+                "At most {MAX_BINDLESS_SAMPLERS} bindless samplers are supported"
+            ));
+        }
+
+        let image_info = ash::vk::DescriptorImageInfo::default().sampler(sampler);
+        let index = self.sampler_count;
+        self.write(
+            device,
+            1,
+            index,
+            ash::vk::DescriptorType::SAMPLER,
+            image_info,
+        );
+        self.sampler_count += 1;
+        Ok(index)
+    }
+
+    fn write(
+        &self,
+        device: &ash::Device,
+        binding: u32,
+        element: u32,
+        ty: ash::vk::DescriptorType,
+        image_info: ash::vk::DescriptorImageInfo,
+    ) {
+        let descriptor_write = ash::vk::WriteDescriptorSet::default()
+            .dst_set(self.descriptor_set)
+            .dst_binding(binding)
+            .dst_array_element(element)
+            .descriptor_type(ty)
+            .image_info(slice::from_ref(&image_info));
+        unsafe { device.update_descriptor_sets(slice::from_ref(&descriptor_write), &[]) };
+    }
+}
@@ -0,0 +1,171 @@
+use crate::input::{bindings, Input};
+use glam::{Mat4, Vec3};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// The scene is laid out with Z pointing up
+const WORLD_UP: Vec3 = Vec3::Z;
+/// Keep the pitch just short of straight up or down, where the view matrix degenerates
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+const MIN_DISTANCE: f32 = 0.1;
+const MAX_DISTANCE: f32 = 50.0;
+/// Movement speed multiplier while sprinting
+const SPRINT_MULTIPLIER: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CameraMode {
+    /// Rotate around `target` at a fixed `distance`
+    Orbit,
+    /// Move freely, looking along the yaw and pitch
+    Fly,
+}
+
+/// A perspective camera, steered by the input bindings.
+///
+/// Holding `look` and moving the mouse, or the `turn` axes, look around. The `move` axes move the
+/// camera, or the target in orbit mode. `zoom` zooms in orbit mode, and changes the movement speed
+/// in fly mode.
+pub(crate) struct Camera {
+    pub mode: CameraMode,
+    pub position: Vec3,
+    /// Point rotated around in orbit mode
+    pub target: Vec3,
+    /// Distance from `position` to `target`
+    pub distance: f32,
+    /// Rotation around the world up axis, in radians, with 0 looking along +X
+    pub yaw: f32,
+    /// Angle above the horizon, in radians
+    pub pitch: f32,
+
+    /// Vertical field of view, in radians
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+
+    /// Units per second
+    pub move_speed: f32,
+    /// Radians per pixel of mouse movement
+    pub look_sensitivity: f32,
+    /// Radians per second at full turn input
+    pub turn_speed: f32,
+}
+
+impl Camera {
+    /// An orbit camera at `position`, looking at `target`.
+    ///
+    /// If the two are the same, the camera looks along +X from `MIN_DISTANCE` away instead.
+    pub fn looking_at(position: Vec3, target: Vec3) -> Self {
+        let offset = target - position;
+        let distance = offset.length().max(MIN_DISTANCE);
+        let direction = offset.try_normalize().unwrap_or(Vec3::X);
+
+        Self {
+            mode: CameraMode::Orbit,
+            position,
+            target,
+            distance,
+            yaw: direction.y.atan2(direction.x),
+            pitch: direction.z.asin().clamp(-MAX_PITCH, MAX_PITCH),
+            fov_y: PI / 4.0,
+            near: 0.1,
+            far: 100.0,
+            move_speed: 2.0,
+            look_sensitivity: 0.005,
+            turn_speed: 2.0,
+        }
+    }
+
+    /// Unit vector the camera is looking along
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        Vec3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), WORLD_UP)
+    }
+
+    /// Perspective projection for Vulkan's clip space, which has Y pointing down
+    pub fn projection(&self, aspect_ratio: f32) -> Mat4 {
+        let mut projection = Mat4::perspective_rh(self.fov_y, aspect_ratio, self.near, self.far);
+        projection.y_axis.y *= -1.0;
+        projection
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => {
+                // Orbit around whatever is in front of the camera
+                self.target = self.position + self.forward() * self.distance;
+                CameraMode::Orbit
+            }
+        };
+    }
+
+    /// Whether the cursor should be hidden and locked to the window, for mouse look in fly mode
+    pub fn captures_cursor(&self, input: &Input) -> bool {
+        self.mode == CameraMode::Fly && input.held(bindings::LOOK)
+    }
+
+    /// Apply this frame's input. Continuous input is scaled by `delta_time` so movement speed
+    /// doesn't depend on the frame rate.
+    pub fn update(&mut self, input: &Input, delta_time: f32) {
+        if input.pressed(bindings::TOGGLE_CAMERA_MODE) {
+            self.toggle_mode();
+        }
+
+        let turn = glam::vec2(input.axis(bindings::TURN_X), input.axis(bindings::TURN_Y));
+        let mut look = turn * self.turn_speed * delta_time;
+        if input.held(bindings::LOOK) {
+            // Mouse movement is already per frame
+            let mouse = glam::vec2(input.axis(bindings::LOOK_X), input.axis(bindings::LOOK_Y));
+            look += mouse * self.look_sensitivity;
+        }
+        // Cursor Y increases downwards, so moving the mouse up looks up
+        self.yaw = (self.yaw - look.x).rem_euclid(2.0 * PI);
+        self.pitch = (self.pitch - look.y).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let zoom = input.axis(bindings::ZOOM);
+        match self.mode {
+            CameraMode::Orbit => {
+                self.distance =
+                    (self.distance * 0.9f32.powf(zoom)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+            }
+            CameraMode::Fly => self.move_speed *= 1.1f32.powf(zoom),
+        }
+
+        let forward = self.forward();
+        // Move along the ground when orbiting, so looking down doesn't move the target into it
+        let forward = match self.mode {
+            CameraMode::Orbit => forward.with_z(0.0).normalize_or_zero(),
+            CameraMode::Fly => forward,
+        };
+        let right = forward.cross(WORLD_UP).normalize_or_zero();
+
+        let direction = forward * input.axis(bindings::MOVE_FORWARD)
+            + right * input.axis(bindings::MOVE_RIGHT)
+            + WORLD_UP * input.axis(bindings::MOVE_UP);
+        // Analog input can move slower, but diagonal movement shouldn't be faster
+        let direction = direction.clamp_length_max(1.0);
+
+        let speed = if input.held(bindings::SPRINT) {
+            self.move_speed * SPRINT_MULTIPLIER
+        } else {
+            self.move_speed
+        };
+        let offset = direction * speed * delta_time;
+
+        match self.mode {
+            CameraMode::Orbit => self.target += offset,
+            CameraMode::Fly => self.position += offset,
+        }
+        self.update_orbit_position();
+    }
+
+    fn update_orbit_position(&mut self) {
+        if self.mode == CameraMode::Orbit {
+            self.position = self.target - self.forward() * self.distance;
+        }
+    }
+}
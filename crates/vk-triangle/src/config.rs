@@ -0,0 +1,42 @@
+use crate::debug_messenger::ValidationConfig;
+use crate::display::DisplayConfig;
+use crate::frame_stats::StatsConfig;
+use crate::frame_sync::FramesConfig;
+use crate::input::InputConfig;
+use crate::logging::LoggingConfig;
+use crate::result::{error, Result};
+use crate::window::WindowConfig;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Settings loaded from `res/config.toml`. Anything missing from the file takes its default.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Config {
+    pub display: DisplayConfig,
+    pub frames: FramesConfig,
+    pub input: InputConfig,
+    pub stats: StatsConfig,
+    pub logging: LoggingConfig,
+    pub validation: ValidationConfig,
+    pub window: WindowConfig,
+}
+
+impl Config {
+    pub const PATH: &str = "res/config.toml";
+
+    /// Load the config at `path`, or the defaults if there's no file there
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("No config at {}, using defaults", path.display());
+                return Ok(Self::default());
+            }
+            Err(e) => return error(&format!("Failed to read {}: {e}", path.display())),
+        };
+
+        toml::from_str(&contents)
+            .or_else(|e| error(&format!("Failed to parse {}: {e}", path.display())))
+    }
+}
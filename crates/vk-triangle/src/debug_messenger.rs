@@ -0,0 +1,409 @@
+use crate::logging::target;
+use crate::result::Result;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::ffi;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The `[validation]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct ValidationConfig {
+    /// Messages less severe than this are dropped by the layers
+    pub min_severity: Severity,
+    /// Kinds of message to report
+    pub message_types: Vec<MessageType>,
+    /// Messages to drop, by their ID name (e.g. `VUID-vkCmdDraw-None-02859`) or number
+    pub suppress: Vec<MessageId>,
+    /// Panic after the frame in which a validation error is reported, so tests fail on them
+    pub panic_on_error: bool,
+
+    /// Check for missing or incorrect synchronization between commands
+    pub synchronization: bool,
+    /// Warn about valid but inadvisable API usage
+    pub best_practices: bool,
+    /// Instrument shaders to check for out of bounds accesses and the like on the GPU
+    pub gpu_assisted: bool,
+    /// Log the output of `debug_printf!` in shaders, whatever `min_severity` is
+    pub debug_printf: bool,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            min_severity: Severity::Info,
+            message_types: vec![
+                MessageType::General,
+                MessageType::Validation,
+                MessageType::Performance,
+            ],
+            suppress: Vec::new(),
+            panic_on_error: false,
+            synchronization: false,
+            best_practices: false,
+            gpu_assisted: false,
+            debug_printf: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Severity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// This severity and every one above it
+    fn and_above(self) -> ash::vk::DebugUtilsMessageSeverityFlagsEXT {
+        use ash::vk::DebugUtilsMessageSeverityFlagsEXT as Vk;
+        [
+            (Self::Verbose, Vk::VERBOSE),
+            (Self::Info, Vk::INFO),
+            (Self::Warning, Vk::WARNING),
+            (Self::Error, Vk::ERROR),
+        ]
+        .into_iter()
+        .filter(|&(severity, _)| severity >= self)
+        .fold(Vk::empty(), |flags, (_, flag)| flags | flag)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MessageType {
+    General,
+    Validation,
+    Performance,
+}
+
+impl MessageType {
+    fn flag(self) -> ash::vk::DebugUtilsMessageTypeFlagsEXT {
+        use ash::vk::DebugUtilsMessageTypeFlagsEXT as Vk;
+        match self {
+            Self::General => Vk::GENERAL,
+            Self::Validation => Vk::VALIDATION,
+            Self::Performance => Vk::PERFORMANCE,
+        }
+    }
+}
+
+/// A message to suppress, by `pMessageIdName` or `messageIdNumber`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum MessageId {
+    Name(String),
+    Number(i32),
+}
+
+/// Totals of the messages reported so far, not counting suppressed ones
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ValidationCounts {
+    pub errors: u64,
+    pub warnings: u64,
+}
+
+/// What the callback needs, pointed to by the messenger's user data
+struct CallbackState {
+    suppressed_names: HashSet<String>,
+    suppressed_numbers: HashSet<i32>,
+    panic_on_error: bool,
+    /// The severities asked for, as more may be enabled to receive printf messages
+    severities: ash::vk::DebugUtilsMessageSeverityFlagsEXT,
+    /// The message types asked for, as more may be enabled to receive printf messages
+    message_types: ash::vk::DebugUtilsMessageTypeFlagsEXT,
+    debug_printf: bool,
+    /// The entry points of each pipeline's stages, by raw pipeline handle
+    entry_points: Mutex<HashMap<u64, Vec<(ash::vk::ShaderStageFlags, &'static str)>>>,
+    errors: AtomicU64,
+    warnings: AtomicU64,
+    /// The first error since `take_error`, kept when `panic_on_error` is set. The callback can't
+    ///  panic itself, as unwinding out of an `extern "system"` fn aborts
+    first_error: Mutex<Option<String>>,
+}
+
+/// Routes messages from the validation layers and drivers to the `log` crate.
+///
+/// It owns the state the callback reads through its user data pointer, so must outlive both the
+///  messenger and the instance, which uses the same callback for its own creation and destruction.
+pub(crate) struct DebugMessenger {
+    state: Box<CallbackState>,
+    severities: ash::vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: ash::vk::DebugUtilsMessageTypeFlagsEXT,
+    validation_features: Vec<ash::vk::ValidationFeatureEnableEXT>,
+    messenger: Option<(
+        ash::ext::debug_utils::Instance,
+        ash::vk::DebugUtilsMessengerEXT,
+    )>,
+}
+
+impl DebugMessenger {
+    pub fn new(config: &ValidationConfig) -> Self {
+        let mut suppressed_names = HashSet::new();
+        let mut suppressed_numbers = HashSet::new();
+        for id in &config.suppress {
+            match id {
+                MessageId::Name(name) => _ = suppressed_names.insert(name.clone()),
+                MessageId::Number(number) => _ = suppressed_numbers.insert(*number),
+            }
+        }
+
+        let validation_features = [
+            (
+                config.synchronization,
+                ash::vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION,
+            ),
+            (
+                config.best_practices,
+                ash::vk::ValidationFeatureEnableEXT::BEST_PRACTICES,
+            ),
+            (
+                config.gpu_assisted,
+                ash::vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+            ),
+            (
+                config.debug_printf,
+                ash::vk::ValidationFeatureEnableEXT::DEBUG_PRINTF,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, feature)| enabled.then_some(feature))
+        .collect();
+        if config.gpu_assisted && config.debug_printf {
+            log::warn!(
+                target: target::VALIDATION,
+                "Older validation layers can't run GPU-assisted validation and debug printf together"
+            );
+        }
+
+        let severities = config.min_severity.and_above();
+        let message_types = config.message_types.iter().fold(
+            ash::vk::DebugUtilsMessageTypeFlagsEXT::empty(),
+            |flags, ty| flags | ty.flag(),
+        );
+
+        Self {
+            state: Box::new(CallbackState {
+                suppressed_names,
+                suppressed_numbers,
+                panic_on_error: config.panic_on_error,
+                severities,
+                message_types,
+                debug_printf: config.debug_printf,
+                entry_points: Mutex::new(HashMap::new()),
+                errors: AtomicU64::new(0),
+                warnings: AtomicU64::new(0),
+                first_error: Mutex::new(None),
+            }),
+            // Printf output comes through as info level validation messages
+            severities: if config.debug_printf {
+                severities | ash::vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+            } else {
+                severities
+            },
+            message_types: if config.debug_printf {
+                message_types | ash::vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+            } else {
+                message_types
+            },
+            validation_features,
+            messenger: None,
+        }
+    }
+
+    /// Info for a messenger using this callback, to chain onto `VkInstanceCreateInfo` or to
+    /// create the messenger with.
+    ///
+    /// The user data points into `self`, so this must not outlive it.
+    pub fn create_info(&self) -> ash::vk::DebugUtilsMessengerCreateInfoEXT<'static> {
+        ash::vk::DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(self.severities)
+            .message_type(self.message_types)
+            .user_data(&*self.state as *const CallbackState as *mut ffi::c_void)
+            .pfn_user_callback(Some(debug_callback))
+    }
+
+    /// The `VkValidationFeaturesEXT` to enable on the instance
+    pub fn validation_features(&self) -> &[ash::vk::ValidationFeatureEnableEXT] {
+        &self.validation_features
+    }
+
+    /// Whether the layers will add code to shaders, which needs the device to allow stores and
+    ///  atomics in all stages
+    pub fn instruments_shaders(&self) -> bool {
+        self.validation_features.iter().any(|&feature| {
+            feature == ash::vk::ValidationFeatureEnableEXT::GPU_ASSISTED
+                || feature == ash::vk::ValidationFeatureEnableEXT::DEBUG_PRINTF
+        })
+    }
+
+    /// Record the entry point of each of `pipeline`'s stages, to say which one printf output
+    ///  came from
+    pub fn add_entry_points(
+        &self,
+        pipeline: ash::vk::Pipeline,
+        entry_points: &[(ash::vk::ShaderStageFlags, &'static str)],
+    ) {
+        use ash::vk::Handle;
+        if !self.state.debug_printf {
+            return;
+        }
+        self.state
+            .entry_points
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(pipeline.as_raw(), entry_points.to_vec());
+    }
+
+    /// Start receiving messages for everything after instance creation.
+    ///
+    /// `instance` must have `VK_EXT_debug_utils` enabled.
+    pub fn register(&mut self, entry: &ash::Entry, instance: &ash::Instance) -> Result<()> {
+        let debug_utils_instance = ash::ext::debug_utils::Instance::new(entry, instance);
+        let messenger = unsafe {
+            debug_utils_instance.create_debug_utils_messenger(&self.create_info(), None)
+        }?;
+        self.messenger = Some((debug_utils_instance, messenger));
+        Ok(())
+    }
+
+    pub fn counts(&self) -> ValidationCounts {
+        ValidationCounts {
+            errors: self.state.errors.load(Ordering::Relaxed),
+            warnings: self.state.warnings.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The first error reported since the last call, if `panic_on_error` is set
+    pub fn take_error(&self) -> Option<String> {
+        if !self.state.panic_on_error {
+            return None;
+        }
+        self.state
+            .first_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Destroy the messenger, before the instance is destroyed.
+    ///
+    /// `self` must still be kept until after the instance is destroyed.
+    pub unsafe fn destroy_messenger(&mut self) {
+        if let Some((debug_utils_instance, messenger)) = self.messenger.take() {
+            unsafe { debug_utils_instance.destroy_debug_utils_messenger(messenger, None) };
+        }
+    }
+}
+
+fn log_level(severity: ash::vk::DebugUtilsMessageSeverityFlagsEXT) -> log::Level {
+    use ash::vk::DebugUtilsMessageSeverityFlagsEXT as Vk;
+    if severity.contains(Vk::ERROR) {
+        log::Level::Error
+    } else if severity.contains(Vk::WARNING) {
+        log::Level::Warn
+    } else if severity.contains(Vk::INFO) {
+        log::Level::Info
+    } else {
+        log::Level::Trace
+    }
+}
+
+unsafe extern "system" fn debug_callback(
+    message_severity: ash::vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: ash::vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const ash::vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    p_user_data: *mut ffi::c_void,
+) -> ash::vk::Bool32 {
+    // Safety: the user data is always a `CallbackState` owned by a live `DebugMessenger`, and
+    //  the callback data is valid for the duration of the callback
+    let state = unsafe { &*(p_user_data as *const CallbackState) };
+    let data = unsafe { &*p_callback_data };
+
+    let id_name = unsafe { data.message_id_name_as_c_str() }
+        .and_then(|name| name.to_str().ok())
+        .unwrap_or_default();
+    if state.suppressed_names.contains(id_name)
+        || state.suppressed_numbers.contains(&data.message_id_number)
+    {
+        return ash::vk::FALSE;
+    }
+    let message = unsafe { data.message_as_c_str() }
+        .and_then(|message| message.to_str().ok())
+        .unwrap_or_default();
+
+    // Named `UNASSIGNED-DEBUG-PRINTF` or `WARNING-DEBUG-PRINTF`, depending on the layer version
+    if state.debug_printf && id_name.ends_with("DEBUG-PRINTF") {
+        let objects = if data.p_objects.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(data.p_objects, data.object_count as usize) }
+        };
+        log_printf(state, objects, message);
+        return ash::vk::FALSE;
+    }
+    if !state.severities.intersects(message_severity)
+        || !state.message_types.intersects(message_types)
+    {
+        return ash::vk::FALSE;
+    }
+
+    let level = log_level(message_severity);
+    match level {
+        log::Level::Error => _ = state.errors.fetch_add(1, Ordering::Relaxed),
+        log::Level::Warn => _ = state.warnings.fetch_add(1, Ordering::Relaxed),
+        _ => {}
+    }
+    log::log!(target: target::VALIDATION, level, "{message_types:?} [{id_name}] {message}");
+
+    if level == log::Level::Error && state.panic_on_error {
+        let mut first_error = state.first_error.lock().unwrap_or_else(|e| e.into_inner());
+        first_error.get_or_insert_with(|| format!("[{id_name}] {message}"));
+    }
+
+    ash::vk::FALSE
+}
+
+/// Log the output of a shader's `debug_printf!` under the `shader` target, with the entry point
+///  it came from
+fn log_printf(
+    state: &CallbackState,
+    objects: &[ash::vk::DebugUtilsObjectNameInfoEXT<'_>],
+    message: &str,
+) {
+    let entry_points = state.entry_points.lock().unwrap_or_else(|e| e.into_inner());
+    let stages = objects
+        .iter()
+        .filter(|object| object.object_type == ash::vk::ObjectType::PIPELINE)
+        .find_map(|object| entry_points.get(&object.object_handle));
+    let entry_point = match stages.map(Vec::as_slice) {
+        Some([(_, entry_point)]) => entry_point,
+        Some(stages) => printf_stage(message)
+            .and_then(|stage| stages.iter().find(|&&(s, _)| s == stage))
+            .map_or("unknown entry point", |(_, entry_point)| entry_point),
+        None => "unknown shader",
+    };
+
+    // The layers describe where the message came from, then a blank line before the output
+    let output = message
+        .split_once("\n\n")
+        .map_or(message, |(_, output)| output)
+        .trim();
+    log::info!(target: target::SHADER, "{entry_point}: {output}");
+}
+
+/// The stage from the `Stage = Vertex.` the layers put in printf messages
+fn printf_stage(message: &str) -> Option<ash::vk::ShaderStageFlags> {
+    let (_, rest) = message.split_once("Stage = ")?;
+    let name = rest.split(|c: char| !c.is_alphanumeric()).next()?;
+    match name {
+        "Vertex" => Some(ash::vk::ShaderStageFlags::VERTEX),
+        "Fragment" => Some(ash::vk::ShaderStageFlags::FRAGMENT),
+        "Compute" => Some(ash::vk::ShaderStageFlags::COMPUTE),
+        _ => None,
+    }
+}
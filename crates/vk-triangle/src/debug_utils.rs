@@ -0,0 +1,80 @@
+use crate::logging::target;
+use std::ffi::CString;
+
+/// Names objects and labels command buffer regions through `VK_EXT_debug_utils`, so validation
+/// messages and captures in tools like RenderDoc are readable.
+///
+/// Every method does nothing when the extension isn't enabled.
+pub(crate) struct DebugUtils {
+    device: Option<ash::ext::debug_utils::Device>,
+}
+
+impl DebugUtils {
+    /// `enabled` must only be true if `VK_EXT_debug_utils` was enabled on `instance`
+    pub fn new(instance: &ash::Instance, device: &ash::Device, enabled: bool) -> Self {
+        Self {
+            device: enabled.then(|| ash::ext::debug_utils::Device::new(instance, device)),
+        }
+    }
+
+    /// Give `handle` a name to show in place of its raw handle
+    pub fn set_name<H: ash::vk::Handle + Copy>(&self, handle: H, name: &str) {
+        let Some(device) = &self.device else {
+            return;
+        };
+        if handle.as_raw() == 0 {
+            return;
+        }
+
+        let name = to_c_string(name);
+        let name_info = ash::vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(handle)
+            .object_name(&name);
+        // Naming is only a debugging aid, so a failure isn't worth stopping for
+        if let Err(e) = unsafe { device.set_debug_utils_object_name(&name_info) } {
+            log::warn!(target: target::DEVICE, "Failed to name {name:?}: {e}");
+        }
+    }
+
+    /// Name each of `handles` as `name[i]`, as for per-frame or per-swapchain-image objects
+    pub fn set_names<H: ash::vk::Handle + Copy>(&self, handles: &[H], name: &str) {
+        if self.device.is_none() {
+            return;
+        }
+        for (i, &handle) in handles.iter().enumerate() {
+            self.set_name(handle, &format!("{name}[{i}]"));
+        }
+    }
+
+    /// Open a labelled region of `command_buffer`, closed by `cmd_end_label`
+    pub fn cmd_begin_label(&self, command_buffer: ash::vk::CommandBuffer, name: &str) {
+        let Some(device) = &self.device else {
+            return;
+        };
+        let name = to_c_string(name);
+        let label = ash::vk::DebugUtilsLabelEXT::default().label_name(&name);
+        unsafe { device.cmd_begin_debug_utils_label(command_buffer, &label) };
+    }
+
+    pub fn cmd_end_label(&self, command_buffer: ash::vk::CommandBuffer) {
+        let Some(device) = &self.device else {
+            return;
+        };
+        unsafe { device.cmd_end_debug_utils_label(command_buffer) };
+    }
+
+    /// Mark a single point in `command_buffer`
+    pub fn cmd_insert_label(&self, command_buffer: ash::vk::CommandBuffer, name: &str) {
+        let Some(device) = &self.device else {
+            return;
+        };
+        let name = to_c_string(name);
+        let label = ash::vk::DebugUtilsLabelEXT::default().label_name(&name);
+        unsafe { device.cmd_insert_debug_utils_label(command_buffer, &label) };
+    }
+}
+
+/// Names are only for display, so drop any interior nul rather than failing
+fn to_c_string(name: &str) -> CString {
+    CString::new(name.replace('\0', "")).expect("Nul bytes were removed")
+}
@@ -0,0 +1,45 @@
+use std::any::Any;
+
+/// Holds objects the GPU may still be using until the frames that could use them have finished,
+/// so they can be replaced without waiting for the device to be idle.
+///
+/// Anything retired is attached to the next submission, and dropped once the fence of that
+/// submission's frame slot has been waited on. Submissions to the queue finish in order, so by
+/// then nothing recorded before the object was retired can still be executing.
+pub(crate) struct DeletionQueue {
+    /// Retired since the last submission
+    pending: Vec<Box<dyn Any>>,
+    /// For each frame in flight, what was retired before its last submission
+    frames: Vec<Vec<Box<dyn Any>>>,
+}
+
+impl DeletionQueue {
+    pub fn new(frames_in_flight: u32) -> Self {
+        Self {
+            pending: Vec::new(),
+            frames: (0..frames_in_flight).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    /// Drop `object` once the GPU has finished with everything submitted so far. Objects are
+    /// dropped in the order they were retired
+    pub fn retire(&mut self, object: impl Any) {
+        self.pending.push(Box::new(object));
+    }
+
+    /// Attach everything retired since the last call to the submission just made for `frame`
+    pub fn submitted(&mut self, frame: usize) {
+        self.frames[frame].append(&mut self.pending);
+    }
+
+    /// Drop what was retired before `frame`'s last submission. Must only be called once `frame`'s
+    /// fence has been waited on
+    pub fn collect(&mut self, frame: usize) {
+        self.frames[frame].clear();
+    }
+
+    /// How many objects are waiting to be dropped
+    pub fn len(&self) -> usize {
+        self.pending.len() + self.frames.iter().map(Vec::len).sum::<usize>()
+    }
+}
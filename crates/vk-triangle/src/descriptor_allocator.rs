@@ -0,0 +1,183 @@
+use crate::logging::target;
+use crate::owned::{self, Owned};
+use crate::result::Result;
+use std::slice;
+
+/// The largest pool a `DescriptorAllocator` grows to, in sets
+const MAX_SETS_PER_POOL: u32 = 4096;
+
+/// How many descriptors of a type to make room for per set in each pool. Sets don't all use every
+/// type, so a ratio can be fractional.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PoolRatio {
+    pub ty: ash::vk::DescriptorType,
+    pub per_set: f32,
+}
+
+/// Allocates descriptor sets from pools it makes as they're needed, each one larger than the last.
+///
+/// Pools are only freed when the allocator is dropped. `reset` returns every set at once, and
+/// makes all the pools available to allocate from again.
+pub(crate) struct DescriptorAllocator {
+    device: owned::Device,
+    ratios: Vec<PoolRatio>,
+    /// Size of the next pool to be made
+    sets_per_pool: u32,
+    /// Pools which may still have room. The last is allocated from first
+    ready: Vec<Owned<ash::vk::DescriptorPool>>,
+    /// Pools which have run out of room since the last reset
+    full: Vec<Owned<ash::vk::DescriptorPool>>,
+}
+
+impl DescriptorAllocator {
+    pub fn new(device: &owned::Device, initial_sets: u32, ratios: &[PoolRatio]) -> Self {
+        Self {
+            device: device.clone(),
+            ratios: ratios.to_vec(),
+            sets_per_pool: initial_sets.clamp(1, MAX_SETS_PER_POOL),
+            ready: Vec::new(),
+            full: Vec::new(),
+        }
+    }
+
+    /// Allocate a set with `layout`, making a new pool if the current ones are out of room
+    pub fn allocate(
+        &mut self,
+        layout: ash::vk::DescriptorSetLayout,
+    ) -> Result<ash::vk::DescriptorSet> {
+        let pool = match self.ready.pop() {
+            Some(pool) => pool,
+            None => self.create_pool()?,
+        };
+
+        let (pool, set) = match self.allocate_from(*pool, layout) {
+            Ok(set) => (pool, set),
+            // Either way this pool can't fit another set like this, so move on to a fresh one
+            Err(
+                ash::vk::Result::ERROR_OUT_OF_POOL_MEMORY | ash::vk::Result::ERROR_FRAGMENTED_POOL,
+            ) => {
+                self.full.push(pool);
+                let pool = self.create_pool()?;
+                let set = self.allocate_from(*pool, layout)?;
+                (pool, set)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        self.ready.push(pool);
+
+        Ok(set)
+    }
+
+    /// Free every set allocated so far. None of them can be in use by the GPU
+    pub fn reset(&mut self) -> Result<()> {
+        self.ready.append(&mut self.full);
+        for pool in &self.ready {
+            unsafe {
+                self.device
+                    .reset_descriptor_pool(**pool, ash::vk::DescriptorPoolResetFlags::empty())
+            }?;
+        }
+        Ok(())
+    }
+
+    fn allocate_from(
+        &self,
+        pool: ash::vk::DescriptorPool,
+        layout: ash::vk::DescriptorSetLayout,
+    ) -> std::result::Result<ash::vk::DescriptorSet, ash::vk::Result> {
+        let alloc_info = ash::vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(slice::from_ref(&layout));
+        unsafe { self.device.allocate_descriptor_sets(&alloc_info) }.map(|sets| sets[0])
+    }
+
+    fn create_pool(&mut self) -> Result<Owned<ash::vk::DescriptorPool>> {
+        let sets = self.sets_per_pool;
+        let pool_sizes: Vec<_> = self
+            .ratios
+            .iter()
+            .map(|ratio| {
+                ash::vk::DescriptorPoolSize::default()
+                    .ty(ratio.ty)
+                    .descriptor_count(((ratio.per_set * sets as f32).ceil() as u32).max(1))
+            })
+            .collect();
+        let pool_info = ash::vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(sets);
+        let pool = unsafe { self.device.create_descriptor_pool(&pool_info, None) }?;
+
+        self.sets_per_pool = (sets + sets / 2).min(MAX_SETS_PER_POOL);
+        log::debug!(
+            target: target::DEVICE,
+            "Created descriptor pool for {sets} sets ({} pools)",
+            self.ready.len() + self.full.len() + 1
+        );
+
+        Ok(unsafe { Owned::new(&self.device, pool) })
+    }
+}
+
+/// Collects descriptors for the bindings of a set, then writes them all at once.
+#[derive(Default)]
+pub(crate) struct DescriptorWriter {
+    buffers: Vec<(u32, ash::vk::DescriptorType, ash::vk::DescriptorBufferInfo)>,
+    images: Vec<(u32, ash::vk::DescriptorType, ash::vk::DescriptorImageInfo)>,
+}
+
+impl DescriptorWriter {
+    /// Bind `range` bytes of `buffer` from `offset`, or the rest of it for `vk::WHOLE_SIZE`
+    pub fn buffer(
+        mut self,
+        binding: u32,
+        ty: ash::vk::DescriptorType,
+        buffer: ash::vk::Buffer,
+        offset: u64,
+        range: u64,
+    ) -> Self {
+        let info = ash::vk::DescriptorBufferInfo::default()
+            .buffer(buffer)
+            .offset(offset)
+            .range(range);
+        self.buffers.push((binding, ty, info));
+        self
+    }
+
+    /// Bind `image_view` in `layout`. `sampler` is ignored by types without one
+    pub fn image(
+        mut self,
+        binding: u32,
+        ty: ash::vk::DescriptorType,
+        image_view: ash::vk::ImageView,
+        sampler: ash::vk::Sampler,
+        layout: ash::vk::ImageLayout,
+    ) -> Self {
+        let info = ash::vk::DescriptorImageInfo::default()
+            .image_view(image_view)
+            .sampler(sampler)
+            .image_layout(layout);
+        self.images.push((binding, ty, info));
+        self
+    }
+
+    /// Write everything collected to `set`, which mustn't be in use by the GPU
+    pub fn update(&self, device: &ash::Device, set: ash::vk::DescriptorSet) {
+        let buffer_writes = self.buffers.iter().map(|(binding, ty, info)| {
+            ash::vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(*binding)
+                .descriptor_type(*ty)
+                .buffer_info(slice::from_ref(info))
+        });
+        let image_writes = self.images.iter().map(|(binding, ty, info)| {
+            ash::vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(*binding)
+                .descriptor_type(*ty)
+                .image_info(slice::from_ref(info))
+        });
+        let writes: Vec<_> = buffer_writes.chain(image_writes).collect();
+
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+    }
+}
@@ -0,0 +1,151 @@
+use serde::Deserialize;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// The `[display]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct DisplayConfig {
+    pub present_mode: PresentMode,
+    /// Cap on the frame rate, enforced on the CPU. 0 for no limit
+    pub max_fps: f32,
+    pub hdr: HdrConfig,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::Mailbox,
+            max_fps: 0.0,
+            hdr: HdrConfig::default(),
+        }
+    }
+}
+
+/// The `[display.hdr]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct HdrConfig {
+    /// Use an HDR surface format when the display supports one
+    pub enabled: bool,
+    /// Brightness in nits of SDR white (a scene value of 1.0) on an HDR display
+    pub paper_white_nits: f32,
+
+    /// Mastering display luminance range in nits, passed on through `VK_EXT_hdr_metadata`
+    pub max_luminance: f32,
+    pub min_luminance: f32,
+    /// Brightest pixel in the content, in nits
+    pub max_content_light_level: f32,
+    /// Brightest average frame in the content, in nits
+    pub max_frame_average_light_level: f32,
+}
+
+impl Default for HdrConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            paper_white_nits: 200.0,
+            max_luminance: 1000.0,
+            min_luminance: 0.001,
+            max_content_light_level: 1000.0,
+            max_frame_average_light_level: 400.0,
+        }
+    }
+}
+
+impl HdrConfig {
+    /// Metadata for the swapchain, assuming content mastered on a Rec.2020 D65 display
+    pub fn metadata(&self) -> ash::vk::HdrMetadataEXT<'static> {
+        let xy = |x, y| ash::vk::XYColorEXT { x, y };
+        ash::vk::HdrMetadataEXT::default()
+            .display_primary_red(xy(0.708, 0.292))
+            .display_primary_green(xy(0.170, 0.797))
+            .display_primary_blue(xy(0.131, 0.046))
+            .white_point(xy(0.3127, 0.3290))
+            .max_luminance(self.max_luminance)
+            .min_luminance(self.min_luminance)
+            .max_content_light_level(self.max_content_light_level)
+            .max_frame_average_light_level(self.max_frame_average_light_level)
+    }
+}
+
+/// How finished frames are queued for display, see `VkPresentModeKHR`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PresentMode {
+    /// Vsync: wait for the next vertical blank, never tearing
+    Fifo,
+    /// Vsync, but tear rather than wait a whole refresh when a frame is late
+    FifoRelaxed,
+    /// Replace the queued frame with the newest one, never tearing or blocking
+    Mailbox,
+    /// No vsync: present straight away, tearing
+    Immediate,
+}
+
+impl PresentMode {
+    /// The mode to switch to when cycling through them with a hotkey
+    pub fn next(self) -> Self {
+        match self {
+            Self::Fifo => Self::FifoRelaxed,
+            Self::FifoRelaxed => Self::Mailbox,
+            Self::Mailbox => Self::Immediate,
+            Self::Immediate => Self::Fifo,
+        }
+    }
+
+    /// The Vulkan present modes to try for this mode, most preferred first.
+    ///
+    /// Each falls back to modes with similar latency, ending with FIFO which is always supported.
+    /// Modes that don't tear never fall back to ones that do.
+    pub fn candidates(self) -> &'static [ash::vk::PresentModeKHR] {
+        use ash::vk::PresentModeKHR as Vk;
+        match self {
+            Self::Fifo => &[Vk::FIFO],
+            Self::FifoRelaxed => &[Vk::FIFO_RELAXED, Vk::FIFO],
+            Self::Mailbox => &[Vk::MAILBOX, Vk::FIFO],
+            Self::Immediate => &[Vk::IMMEDIATE, Vk::MAILBOX, Vk::FIFO],
+        }
+    }
+}
+
+/// Caps the frame rate by sleeping until the next frame is due
+pub(crate) struct FrameLimiter {
+    frame_time: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    /// Sleeping can overshoot by about a scheduler tick, so spin for the last part of the wait
+    const SPIN_TIME: Duration = Duration::from_micros(1500);
+
+    /// A limiter allowing at most `max_fps` frames per second, or no limit if it's not positive
+    pub fn new(max_fps: f32) -> Self {
+        Self {
+            frame_time: (max_fps > 0.0).then(|| Duration::from_secs_f32(1.0 / max_fps)),
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Block until the next frame should start
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else {
+            return;
+        };
+
+        let now = Instant::now();
+        if now >= self.next_frame {
+            // Running behind, so start counting again from now rather than trying to catch up
+            self.next_frame = now + frame_time;
+            return;
+        }
+
+        if let Some(sleep_time) = (self.next_frame - now).checked_sub(Self::SPIN_TIME) {
+            sleep(sleep_time);
+        }
+        while Instant::now() < self.next_frame {
+            std::hint::spin_loop();
+        }
+        self.next_frame += frame_time;
+    }
+}
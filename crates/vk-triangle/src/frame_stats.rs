@@ -0,0 +1,680 @@
+use crate::logging::target;
+use crate::result::{error, Result};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The `[stats]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct StatsConfig {
+    /// Number of recent frames the periodic percentiles are taken over. Unless benchmarking or
+    ///  exporting, only these are kept, so the report on exit covers them too
+    pub rolling_window: usize,
+    /// Seconds between logging the rolling stats, 0 to only log the report on exit
+    pub report_interval: f32,
+    /// Write every frame's timings here on exit, as CSV or JSON depending on the extension
+    pub export: Option<PathBuf>,
+    pub benchmark: BenchmarkConfig,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            rolling_window: 600,
+            report_interval: 5.0,
+            export: None,
+            benchmark: BenchmarkConfig::default(),
+        }
+    }
+}
+
+/// The `[stats.benchmark]` section of the config file.
+///
+/// Setting either `frames` or `duration` runs a benchmark, exiting once the first is reached.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct BenchmarkConfig {
+    /// Frames to measure, 0 for no limit
+    pub frames: u64,
+    /// Seconds to measure for, 0 for no limit
+    pub duration: f32,
+    /// Frames to run before measuring, so startup costs don't skew the results
+    pub warmup_frames: u64,
+    /// Append the run's frames in flight, throughput and latency to this CSV when it finishes,
+    ///  then log every run in it, to compare runs with different `[frames] in_flight`
+    pub comparison: Option<PathBuf>,
+}
+
+impl BenchmarkConfig {
+    pub fn enabled(&self) -> bool {
+        self.frames > 0 || self.duration > 0.0
+    }
+}
+
+/// The parts of `draw_frame` timed on the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FramePhase {
+    /// Waiting for the GPU to finish with this frame's resources
+    WaitFence,
+    AcquireImage,
+    /// Updating per-frame buffers and recording the command buffer
+    Record,
+    Submit,
+    Present,
+}
+
+impl FramePhase {
+    pub const COUNT: usize = 5;
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::WaitFence,
+        Self::AcquireImage,
+        Self::Record,
+        Self::Submit,
+        Self::Present,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::WaitFence => "wait_fence",
+            Self::AcquireImage => "acquire",
+            Self::Record => "record",
+            Self::Submit => "submit",
+            Self::Present => "present",
+        }
+    }
+}
+
+/// Timings for a single frame
+#[derive(Debug, Clone, Default)]
+struct FrameSample {
+    /// Time since the previous frame ended, so including everything outside `draw_frame`
+    frame_time: Duration,
+    phases: [Duration; FramePhase::COUNT],
+    /// GPU time per named region. These are read back when the frame's resources are reused, so
+    ///  they're for the frame `frames_in_flight` before this one
+    gpu: Vec<(&'static str, Duration)>,
+    /// Time from the frame `frames_in_flight` before this one starting, to the CPU seeing the GPU
+    ///  finish it. Only an upper bound if the GPU finished before the CPU waited for it
+    latency: Option<Duration>,
+}
+
+/// Upper edges of the histogram buckets in milliseconds, with a final bucket for anything slower
+const HISTOGRAM_EDGES_MS: [f64; 10] = [1.0, 2.0, 4.0, 6.94, 8.33, 11.1, 16.7, 33.3, 50.0, 100.0];
+
+/// Records how long each frame and each phase of it takes, logging rolling percentiles as the
+/// app runs and a full report on exit.
+pub(crate) struct FrameStats {
+    config: StatsConfig,
+    frames_in_flight: u32,
+    /// The measured frames, oldest first. Every frame since startup, or since the end of the
+    ///  benchmark warmup, if `keep_all`, otherwise a ring buffer of the last `rolling_window`
+    samples: VecDeque<FrameSample>,
+    /// Whether every sample is needed, for a benchmark or an export
+    keep_all: bool,
+    /// Frames measured, including those dropped from `samples`
+    measured_frames: u64,
+    /// Total frame time of the measured frames
+    measured_time: Duration,
+    /// The frame currently being timed
+    current: FrameSample,
+    /// Frames seen, including warmup frames which aren't recorded
+    frame_count: u64,
+    /// When the previous frame ended
+    last_frame: Instant,
+    /// When the last phase of the current frame ended
+    phase_mark: Instant,
+    /// When each frame the GPU hasn't been seen to finish started, oldest first
+    frame_starts: VecDeque<Instant>,
+    /// When the first recorded frame started
+    measure_start: Instant,
+    last_report: Instant,
+}
+
+impl FrameStats {
+    pub fn new(config: StatsConfig, frames_in_flight: u32) -> Result<Self> {
+        if config.rolling_window == 0 {
+            return error("stats.rolling_window must be at least 1");
+        }
+
+        let now = Instant::now();
+        if config.benchmark.enabled() {
+            log::info!(target: target::FRAME, "Running benchmark: {:?}", config.benchmark);
+        }
+        let keep_all = config.benchmark.enabled() || config.export.is_some();
+        let samples = if keep_all {
+            VecDeque::new()
+        } else {
+            VecDeque::with_capacity(config.rolling_window)
+        };
+        Ok(Self {
+            config,
+            frames_in_flight,
+            samples,
+            keep_all,
+            measured_frames: 0,
+            measured_time: Duration::ZERO,
+            current: FrameSample::default(),
+            frame_count: 0,
+            last_frame: now,
+            phase_mark: now,
+            frame_starts: VecDeque::new(),
+            measure_start: now,
+            last_report: now,
+        })
+    }
+
+    /// Start timing the phases of a frame, from now
+    pub fn begin_phases(&mut self) {
+        self.phase_mark = Instant::now();
+        self.frame_starts.push_back(self.phase_mark);
+    }
+
+    /// Record the latency of the frame `frames_in_flight` before the current one, once the GPU
+    /// has finished it
+    pub fn record_latency(&mut self) {
+        if self.frame_starts.len() > self.frames_in_flight as usize {
+            let start = self.frame_starts.pop_front().unwrap();
+            self.current.latency = Some(start.elapsed());
+        }
+    }
+
+    /// Add the time since the last phase ended (or `begin_phases`) to `phase`
+    pub fn end_phase(&mut self, phase: FramePhase) {
+        let now = Instant::now();
+        self.current.phases[phase as usize] += now - self.phase_mark;
+        self.phase_mark = now;
+    }
+
+    /// Attach GPU region timings to the current frame
+    pub fn record_gpu_times(&mut self, times: Vec<(&'static str, Duration)>) {
+        self.current.gpu = times;
+    }
+
+    /// Finish timing the current frame
+    pub fn end_frame(&mut self) {
+        let now = Instant::now();
+        self.current.frame_time = now - self.last_frame;
+        self.last_frame = now;
+        self.frame_count += 1;
+
+        let sample = std::mem::take(&mut self.current);
+        if self.frame_count <= self.config.benchmark.warmup_frames {
+            self.measure_start = now;
+            return;
+        }
+        if !self.keep_all && self.samples.len() == self.config.rolling_window {
+            self.samples.pop_front();
+        }
+        self.measured_frames += 1;
+        self.measured_time += sample.frame_time;
+        self.samples.push_back(sample);
+
+        let report_interval = self.config.report_interval;
+        if report_interval > 0.0 && (now - self.last_report).as_secs_f32() >= report_interval {
+            self.last_report = now;
+            let start = self
+                .samples
+                .len()
+                .saturating_sub(self.config.rolling_window);
+            let summary = Summary::new(self.samples.range(start..));
+            log::info!(
+                target: target::FRAME,
+                "Last {} frames: mean {:.2}ms ({:.0}fps), p50 {:.2}ms, p95 {:.2}ms, p99 {:.2}ms",
+                self.samples.len() - start,
+                summary.mean_ms,
+                1000.0 / summary.mean_ms,
+                summary.p50_ms,
+                summary.p95_ms,
+                summary.p99_ms,
+            );
+        }
+    }
+
+    /// Whether the benchmark has run for long enough, and the app should exit
+    pub fn benchmark_finished(&self) -> bool {
+        let benchmark = &self.config.benchmark;
+        let frames_done = benchmark.frames > 0 && self.measured_frames >= benchmark.frames;
+        let duration_done = benchmark.duration > 0.0
+            && !self.samples.is_empty()
+            && self.measure_start.elapsed().as_secs_f32() >= benchmark.duration;
+        frames_done || duration_done
+    }
+
+    /// Log the summary of every recorded frame, and export them if configured to
+    pub fn report(&self) {
+        if self.samples.is_empty() {
+            log::info!(target: target::FRAME, "No frames recorded");
+            return;
+        }
+
+        let summary = Summary::new(&self.samples);
+        let mut frames = format!(
+            "{} frames in {:.2}s",
+            self.measured_frames,
+            self.measured_time.as_secs_f64()
+        );
+        if self.samples.len() as u64 != self.measured_frames {
+            _ = write!(frames, ", stats are for the last {}", self.samples.len());
+        }
+        log::info!(target: target::FRAME, "{frames}");
+        log::info!(
+            target: target::FRAME,
+            "Frame time: mean {:.3}ms ({:.1}fps), min {:.3}ms, max {:.3}ms",
+            summary.mean_ms,
+            1000.0 / summary.mean_ms,
+            summary.min_ms,
+            summary.max_ms
+        );
+        log::info!(
+            target: target::FRAME,
+            "Percentiles: p50 {:.3}ms, p95 {:.3}ms, p99 {:.3}ms",
+            summary.p50_ms, summary.p95_ms, summary.p99_ms
+        );
+        if let Some(latency) = self.latency_summary() {
+            log::info!(
+                target: target::FRAME,
+                "Latency ({} in flight): mean {:.3}ms, p50 {:.3}ms, p95 {:.3}ms, p99 {:.3}ms",
+                self.frames_in_flight,
+                latency.mean_ms,
+                latency.p50_ms,
+                latency.p95_ms,
+                latency.p99_ms
+            );
+        }
+
+        log::info!(target: target::FRAME, "CPU time per phase:");
+        for phase in FramePhase::ALL {
+            let mean = self
+                .samples
+                .iter()
+                .map(|sample| sample.phases[phase as usize])
+                .sum::<Duration>()
+                / self.samples.len() as u32;
+            log::info!(target: target::FRAME, "  {:<10} {:.3}ms", phase.name(), as_ms(mean));
+        }
+
+        let gpu_regions = self.gpu_regions();
+        if !gpu_regions.is_empty() {
+            log::info!(target: target::FRAME, "GPU time per region:");
+        }
+        for name in gpu_regions {
+            let times: Vec<Duration> = self
+                .samples
+                .iter()
+                .filter_map(|sample| sample.gpu_time(name))
+                .collect();
+            let mean = times.iter().sum::<Duration>() / times.len() as u32;
+            let max = times.iter().max().copied().unwrap_or_default();
+            log::info!(
+                target: target::FRAME,
+                "  {name:<10} mean {:.3}ms, max {:.3}ms",
+                as_ms(mean),
+                as_ms(max)
+            );
+        }
+
+        log::info!(target: target::FRAME, "Histogram:");
+        for line in self.histogram().lines() {
+            log::info!(target: target::FRAME, "{line}");
+        }
+
+        if let Some(path) = &self.config.export {
+            match self.export(path) {
+                Ok(()) => {
+                    log::info!(target: target::FRAME, "Exported frame stats to {}", path.display())
+                }
+                Err(e) => log::error!(target: target::FRAME, "{e}"),
+            }
+        }
+
+        if let Some(path) = &self.config.benchmark.comparison {
+            if let Err(e) = self.compare(path) {
+                log::error!(target: target::FRAME, "{e}");
+            }
+        }
+    }
+
+    /// Latency over every sample that has one, if any do
+    fn latency_summary(&self) -> Option<Summary> {
+        let latencies: Vec<f64> = self
+            .samples
+            .iter()
+            .filter_map(|sample| sample.latency.map(as_ms))
+            .collect();
+        (!latencies.is_empty()).then(|| Summary::from_ms(latencies))
+    }
+
+    /// Append this run to the comparison CSV at `path`, then log every run in it
+    fn compare(&self, path: &Path) -> Result<()> {
+        const HEADER: &str = "frames_in_flight,frames,mean_fps,mean_frame_ms,p95_frame_ms,\
+                              mean_latency_ms,p95_latency_ms";
+
+        let summary = Summary::new(&self.samples);
+        let (mean_latency, p95_latency) = match self.latency_summary() {
+            Some(latency) => (latency.mean_ms, latency.p95_ms),
+            None => (f64::NAN, f64::NAN),
+        };
+        let mut row = String::new();
+        if !path.exists() {
+            _ = writeln!(row, "{HEADER}");
+        }
+        _ = writeln!(
+            row,
+            "{},{},{:.2},{:.3},{:.3},{:.3},{:.3}",
+            self.frames_in_flight,
+            self.samples.len(),
+            1000.0 / summary.mean_ms,
+            summary.mean_ms,
+            summary.p95_ms,
+            mean_latency,
+            p95_latency
+        );
+
+        let write_failed = |e| error(&format!("Failed to write to {}: {e}", path.display()));
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(row.as_bytes()))
+            .or_else(write_failed)?;
+        let contents = std::fs::read_to_string(path)
+            .or_else(|e| error(&format!("Failed to read {}: {e}", path.display())))?;
+
+        log::info!(target: target::FRAME, "Benchmark runs in {}:", path.display());
+        for line in contents.lines() {
+            let columns = line.split(',').fold(String::new(), |mut columns, column| {
+                _ = write!(columns, "{column:>17}");
+                columns
+            });
+            log::info!(target: target::FRAME, "  {columns}");
+        }
+        Ok(())
+    }
+
+    fn histogram(&self) -> String {
+        const BAR_WIDTH: usize = 50;
+
+        let mut counts = [0usize; HISTOGRAM_EDGES_MS.len() + 1];
+        for sample in &self.samples {
+            counts[histogram_bucket(as_ms(sample.frame_time))] += 1;
+        }
+
+        let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+        let mut lower = 0.0;
+        let mut out = String::new();
+        for (i, &count) in counts.iter().enumerate() {
+            let label = match HISTOGRAM_EDGES_MS.get(i) {
+                Some(upper) => format!("{lower:>6.2}-{upper:<6.2}ms"),
+                None => format!("{lower:>6.2}+      ms"),
+            };
+            let bar = "#".repeat(count * BAR_WIDTH / max_count);
+            _ = writeln!(out, "  {label} {count:>7} {bar}");
+            lower = HISTOGRAM_EDGES_MS.get(i).copied().unwrap_or(lower);
+        }
+        out
+    }
+
+    /// Write every sample to `path`, as JSON if it ends in `.json` and CSV otherwise
+    fn export(&self, path: &Path) -> Result<()> {
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension == "json");
+        let contents = if is_json {
+            self.to_json()
+        } else {
+            self.to_csv()
+        };
+        std::fs::write(path, contents)
+            .or_else(|e| error(&format!("Failed to export to {}: {e}", path.display())))
+    }
+
+    /// Names of every GPU region in the samples, in the order they first appear
+    fn gpu_regions(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        for (name, _) in self.samples.iter().flat_map(|sample| &sample.gpu) {
+            if !names.contains(name) {
+                names.push(*name);
+            }
+        }
+        names
+    }
+
+    fn to_csv(&self) -> String {
+        let gpu_regions = self.gpu_regions();
+
+        let mut out = String::from("frame,frame_time_ms");
+        for phase in FramePhase::ALL {
+            _ = write!(out, ",{}_ms", phase.name());
+        }
+        for name in &gpu_regions {
+            _ = write!(out, ",gpu_{name}_ms");
+        }
+        out.push_str(",latency_ms\n");
+
+        for (i, sample) in self.samples.iter().enumerate() {
+            _ = write!(out, "{i},{}", as_ms(sample.frame_time));
+            for phase in sample.phases {
+                _ = write!(out, ",{}", as_ms(phase));
+            }
+            // Left empty for frames without a time for the region
+            for name in &gpu_regions {
+                out.push(',');
+                if let Some(time) = sample.gpu_time(name) {
+                    _ = write!(out, "{}", as_ms(time));
+                }
+            }
+            out.push(',');
+            if let Some(latency) = sample.latency {
+                _ = write!(out, "{}", as_ms(latency));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn to_json(&self) -> String {
+        let summary = Summary::new(&self.samples);
+        let mut out = String::from("{\n");
+        _ = writeln!(
+            out,
+            "  \"summary\": {{\"frames\": {}, \"frames_in_flight\": {}, \"mean_ms\": {}, \
+             \"min_ms\": {}, \"max_ms\": {}, \"p50_ms\": {}, \"p95_ms\": {}, \"p99_ms\": {}}},",
+            self.samples.len(),
+            self.frames_in_flight,
+            summary.mean_ms,
+            summary.min_ms,
+            summary.max_ms,
+            summary.p50_ms,
+            summary.p95_ms,
+            summary.p99_ms
+        );
+
+        out.push_str("  \"frames\": [\n");
+        for (i, sample) in self.samples.iter().enumerate() {
+            _ = write!(out, "    {{\"frame_time_ms\": {}", as_ms(sample.frame_time));
+            for phase in FramePhase::ALL {
+                let time = sample.phases[phase as usize];
+                _ = write!(out, ", \"{}_ms\": {}", phase.name(), as_ms(time));
+            }
+            out.push_str(", \"gpu_ms\": {");
+            for (j, (name, time)) in sample.gpu.iter().enumerate() {
+                let separator = if j > 0 { ", " } else { "" };
+                _ = write!(out, "{separator}\"{name}\": {}", as_ms(*time));
+            }
+            out.push('}');
+            if let Some(latency) = sample.latency {
+                _ = write!(out, ", \"latency_ms\": {}", as_ms(latency));
+            }
+            let separator = if i + 1 < self.samples.len() { "," } else { "" };
+            _ = writeln!(out, "}}{separator}");
+        }
+        out.push_str("  ]\n}\n");
+        out
+    }
+}
+
+impl FrameSample {
+    fn gpu_time(&self, name: &str) -> Option<Duration> {
+        self.gpu
+            .iter()
+            .find(|(region, _)| *region == name)
+            .map(|&(_, time)| time)
+    }
+}
+
+/// Statistics over some times, in milliseconds
+struct Summary {
+    mean_ms: f64,
+    min_ms: f64,
+    max_ms: f64,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+}
+
+impl Summary {
+    /// Frame times over `samples`, which must not be empty
+    fn new<'a>(samples: impl IntoIterator<Item = &'a FrameSample>) -> Self {
+        Self::from_ms(
+            samples
+                .into_iter()
+                .map(|sample| as_ms(sample.frame_time))
+                .collect(),
+        )
+    }
+
+    /// `times` must not be empty
+    fn from_ms(mut times: Vec<f64>) -> Self {
+        times.sort_by(f64::total_cmp);
+
+        // Nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * times.len() as f64).ceil() as usize;
+            times[rank.clamp(1, times.len()) - 1]
+        };
+
+        Self {
+            mean_ms: times.iter().sum::<f64>() / times.len() as f64,
+            min_ms: times[0],
+            max_ms: times[times.len() - 1],
+            p50_ms: percentile(50.0),
+            p95_ms: percentile(95.0),
+            p99_ms: percentile(99.0),
+        }
+    }
+}
+
+/// Index into the histogram of the bucket for a frame time of `ms`. Buckets include their upper
+/// edge
+fn histogram_bucket(ms: f64) -> usize {
+    HISTOGRAM_EDGES_MS.partition_point(|&edge| edge < ms)
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stats keeping every sample, holding a frame for each of `frame_times_ms`
+    fn stats_with_frames(frame_times_ms: &[u64]) -> FrameStats {
+        let config = StatsConfig {
+            export: Some(PathBuf::from("frames.csv")),
+            ..StatsConfig::default()
+        };
+        let mut stats = FrameStats::new(config, 2).unwrap();
+        for &ms in frame_times_ms {
+            stats.samples.push_back(FrameSample {
+                frame_time: Duration::from_millis(ms),
+                phases: [Duration::from_millis(1); FramePhase::COUNT],
+                gpu: vec![("frame", Duration::from_millis(ms / 2))],
+                latency: Some(Duration::from_millis(2 * ms)),
+            });
+        }
+        stats
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let summary = Summary::from_ms((1..=100).map(f64::from).collect());
+        assert_eq!(summary.p50_ms, 50.0);
+        assert_eq!(summary.p95_ms, 95.0);
+        assert_eq!(summary.p99_ms, 99.0);
+        assert_eq!(summary.min_ms, 1.0);
+        assert_eq!(summary.max_ms, 100.0);
+        assert_eq!(summary.mean_ms, 50.5);
+    }
+
+    #[test]
+    fn percentiles_of_a_single_time() {
+        let summary = Summary::from_ms(vec![7.0]);
+        assert_eq!(summary.p50_ms, 7.0);
+        assert_eq!(summary.p99_ms, 7.0);
+    }
+
+    #[test]
+    fn histogram_buckets_include_their_upper_edge() {
+        assert_eq!(histogram_bucket(0.5), 0);
+        assert_eq!(histogram_bucket(1.0), 0);
+        assert_eq!(histogram_bucket(1.5), 1);
+        assert_eq!(histogram_bucket(11.2), 6);
+        assert_eq!(histogram_bucket(16.7), 6);
+        assert_eq!(histogram_bucket(16.71), 7);
+        assert_eq!(histogram_bucket(100.0), 9);
+        assert_eq!(histogram_bucket(250.0), HISTOGRAM_EDGES_MS.len());
+    }
+
+    #[test]
+    fn histogram_counts_every_frame() {
+        let stats = stats_with_frames(&[1, 3, 3, 500]);
+        let histogram = stats.histogram();
+        let counts: Vec<usize> = histogram
+            .lines()
+            .map(|line| {
+                let (_label, rest) = line.split_once("ms").unwrap();
+                rest.split_whitespace().next().unwrap().parse().unwrap()
+            })
+            .collect();
+        assert_eq!(counts.len(), HISTOGRAM_EDGES_MS.len() + 1);
+        assert_eq!(counts.iter().sum::<usize>(), 4);
+        assert!(histogram.lines().nth(2).unwrap().contains("      2 ##"));
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let stats = stats_with_frames(&[10, 20]);
+        let csv = stats.to_csv();
+        let mut lines = csv.lines();
+
+        let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(header[..2], ["frame", "frame_time_ms"]);
+        assert!(header.contains(&"gpu_frame_ms"));
+        assert_eq!(header.last(), Some(&"latency_ms"));
+
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            assert_eq!(row.len(), header.len());
+        }
+        assert_eq!(rows[1][0], "1");
+        assert_eq!(rows[1][1].parse::<f64>().unwrap(), 20.0);
+        assert_eq!(rows[1][header.len() - 1].parse::<f64>().unwrap(), 40.0);
+    }
+
+    #[test]
+    fn json_has_a_summary_and_every_frame() {
+        let stats = stats_with_frames(&[10, 20]);
+        let json = stats.to_json();
+        assert!(json.contains("\"frames\": 2, \"frames_in_flight\": 2"));
+        assert!(json.contains("\"mean_ms\": 15"));
+        assert_eq!(json.matches("\"frame_time_ms\"").count(), 2);
+        assert!(json.contains("\"gpu_ms\": {\"frame\": 5}"));
+        assert!(json.trim_end().ends_with('}'));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::debug_utils::DebugUtils;
+use crate::owned::{self, Owned};
+use crate::result::{error, Result};
+use serde::Deserialize;
+use std::slice;
+
+/// The most frames `[frames] in_flight` can allow, as more only adds latency
+pub(crate) const MAX_FRAMES_IN_FLIGHT: u32 = 4;
+
+/// The `[frames]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct FramesConfig {
+    /// Frames the CPU can get ahead of the GPU, from 1 to `MAX_FRAMES_IN_FLIGHT`. Each has its own
+    ///  command buffer, per-frame buffers and descriptor sets
+    pub in_flight: u32,
+    /// Pace frames with a Vulkan 1.2 timeline semaphore rather than a fence per frame in flight,
+    /// where the device supports them
+    pub timeline_semaphores: bool,
+}
+
+impl Default for FramesConfig {
+    fn default() -> Self {
+        Self {
+            in_flight: 2,
+            timeline_semaphores: false,
+        }
+    }
+}
+
+impl FramesConfig {
+    /// `in_flight`, if it's in range
+    pub fn frames_in_flight(&self) -> Result<u32> {
+        if !(1..=MAX_FRAMES_IN_FLIGHT).contains(&self.in_flight) {
+            return error(&format!(
+                "frames.in_flight must be from 1 to {MAX_FRAMES_IN_FLIGHT}, but is {}",
+                self.in_flight
+            ));
+        }
+        Ok(self.in_flight)
+    }
+}
+
+/// A value of a timeline semaphore, for a submission to signal or wait for
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TimelinePoint {
+    pub semaphore: ash::vk::Semaphore,
+    pub value: u64,
+}
+
+/// A timeline semaphore counting the submissions to one queue. Each submission signals the next
+/// value, so other queues and the CPU can wait for any submission by its value.
+pub(crate) struct Timeline {
+    semaphore: Owned<ash::vk::Semaphore>,
+    /// The value signalled by the latest submission, or 0 before the first
+    last: u64,
+}
+
+impl Timeline {
+    pub fn new(device: &owned::Device) -> Result<Self> {
+        let mut type_info = ash::vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(ash::vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let semaphore_info = ash::vk::SemaphoreCreateInfo::default().push_next(&mut type_info);
+        let semaphore = unsafe { device.create_semaphore(&semaphore_info, None) }?;
+
+        Ok(Self {
+            semaphore: unsafe { Owned::new(device, semaphore) },
+            last: 0,
+        })
+    }
+
+    pub fn semaphore(&self) -> ash::vk::Semaphore {
+        *self.semaphore
+    }
+
+    /// The point signalled by the latest submission, e.g. for a submission to another queue to
+    /// wait for
+    #[allow(dead_code)] // For transfer and compute queues, which nothing submits to yet
+    pub fn last_point(&self) -> TimelinePoint {
+        TimelinePoint {
+            semaphore: *self.semaphore,
+            value: self.last,
+        }
+    }
+
+    /// Reserve the point for the next submission to signal
+    pub fn next_point(&mut self) -> TimelinePoint {
+        self.last += 1;
+        TimelinePoint {
+            semaphore: *self.semaphore,
+            value: self.last,
+        }
+    }
+
+    /// Block until the GPU has signalled `value`
+    pub fn wait(&self, device: &ash::Device, value: u64) -> Result<()> {
+        let wait_info = ash::vk::SemaphoreWaitInfo::default()
+            .semaphores(slice::from_ref(&*self.semaphore))
+            .values(slice::from_ref(&value));
+        unsafe { device.wait_semaphores(&wait_info, u64::MAX) }?;
+        Ok(())
+    }
+}
+
+/// The semaphores for a queue submission, mixing binary semaphores (which the swapchain needs)
+/// with timeline points.
+#[derive(Default)]
+pub(crate) struct Submit {
+    wait_semaphores: Vec<ash::vk::Semaphore>,
+    /// Ignored for binary semaphores
+    wait_values: Vec<u64>,
+    wait_stages: Vec<ash::vk::PipelineStageFlags>,
+    signal_semaphores: Vec<ash::vk::Semaphore>,
+    /// Ignored for binary semaphores
+    signal_values: Vec<u64>,
+}
+
+impl Submit {
+    /// Wait for binary `semaphore` before `stage`
+    pub fn wait_binary(
+        mut self,
+        semaphore: ash::vk::Semaphore,
+        stage: ash::vk::PipelineStageFlags,
+    ) -> Self {
+        self.wait_semaphores.push(semaphore);
+        self.wait_values.push(0);
+        self.wait_stages.push(stage);
+        self
+    }
+
+    /// Wait for `point` before `stage`, e.g. for a transfer or compute submission to finish
+    #[allow(dead_code)] // For transfer and compute queues, which nothing submits to yet
+    pub fn wait(mut self, point: TimelinePoint, stage: ash::vk::PipelineStageFlags) -> Self {
+        self.wait_semaphores.push(point.semaphore);
+        self.wait_values.push(point.value);
+        self.wait_stages.push(stage);
+        self
+    }
+
+    pub fn signal_binary(mut self, semaphore: ash::vk::Semaphore) -> Self {
+        self.signal_semaphores.push(semaphore);
+        self.signal_values.push(0);
+        self
+    }
+
+    pub fn signal(mut self, point: TimelinePoint) -> Self {
+        self.signal_semaphores.push(point.semaphore);
+        self.signal_values.push(point.value);
+        self
+    }
+
+    /// # Safety
+    /// Every semaphore, `command_buffers` and `fence` must be from `device`, and `fence` must be
+    /// null or unsignalled
+    pub unsafe fn submit(
+        &self,
+        device: &ash::Device,
+        queue: ash::vk::Queue,
+        command_buffers: &[ash::vk::CommandBuffer],
+        fence: ash::vk::Fence,
+    ) -> Result<()> {
+        let mut timeline_info = ash::vk::TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(&self.wait_values)
+            .signal_semaphore_values(&self.signal_values);
+        let submit_info = ash::vk::SubmitInfo::default()
+            .wait_semaphores(&self.wait_semaphores)
+            .wait_dst_stage_mask(&self.wait_stages)
+            .command_buffers(command_buffers)
+            .signal_semaphores(&self.signal_semaphores);
+        // The values only matter to timeline semaphores, which need Vulkan 1.2
+        let submit_info = if self.uses_timelines() {
+            submit_info.push_next(&mut timeline_info)
+        } else {
+            submit_info
+        };
+
+        unsafe { device.queue_submit(queue, slice::from_ref(&submit_info), fence) }?;
+        Ok(())
+    }
+
+    fn uses_timelines(&self) -> bool {
+        self.wait_values
+            .iter()
+            .chain(&self.signal_values)
+            .any(|&value| value != 0)
+    }
+}
+
+/// Paces frames so at most `frames_in_flight` are submitted to the GPU but unfinished, using
+/// either a fence per frame in flight or a single timeline semaphore.
+///
+/// Frames are numbered from 0 in the order they're submitted. Resources duplicated per frame in
+/// flight are indexed by `slot`.
+pub(crate) struct FrameSync {
+    frames_in_flight: u32,
+    /// The number of the next frame to be submitted
+    frame: u64,
+    mode: Mode,
+}
+
+enum Mode {
+    /// Each frame signals the fence of its slot, which starts signalled as if for a previous frame
+    Fences(Vec<Owned<ash::vk::Fence>>),
+    /// Frame `n` signals `n + 1`
+    Timeline(Timeline),
+}
+
+impl FrameSync {
+    /// `timeline` must only be true if the `timelineSemaphore` feature is enabled
+    pub fn new(device: &owned::Device, frames_in_flight: u32, timeline: bool) -> Result<Self> {
+        let mode = if timeline {
+            Mode::Timeline(Timeline::new(device)?)
+        } else {
+            let fence_info =
+                ash::vk::FenceCreateInfo::default().flags(ash::vk::FenceCreateFlags::SIGNALED);
+            let fences = (0..frames_in_flight)
+                .map(|_| {
+                    let fence = unsafe { device.create_fence(&fence_info, None) }?;
+                    Ok(unsafe { Owned::new(device, fence) })
+                })
+                .collect::<Result<_>>()?;
+            Mode::Fences(fences)
+        };
+
+        Ok(Self {
+            frames_in_flight,
+            frame: 0,
+            mode,
+        })
+    }
+
+    /// Whether frames are paced with a timeline semaphore
+    pub fn uses_timeline(&self) -> bool {
+        matches!(self.mode, Mode::Timeline(_))
+    }
+
+    pub fn frames_in_flight(&self) -> u32 {
+        self.frames_in_flight
+    }
+
+    /// The number of the next frame to be submitted
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Index of the next frame's per-frame resources
+    pub fn slot(&self) -> usize {
+        (self.frame % self.frames_in_flight as u64) as usize
+    }
+
+    /// Block until the last frame to use the next frame's slot has finished, so its resources can
+    /// be reused
+    pub fn wait_for_slot(&self, device: &ash::Device) -> Result<()> {
+        match self.frame.checked_sub(self.frames_in_flight as u64) {
+            Some(frame) => self.wait_for_frame(device, frame),
+            None => Ok(()),
+        }
+    }
+
+    /// Block until `frame` has finished on the GPU. It must already have been submitted
+    pub fn wait_for_frame(&self, device: &ash::Device, frame: u64) -> Result<()> {
+        match &self.mode {
+            // The slot's fence may belong to a later frame by now, which can only finish after
+            Mode::Fences(fences) => {
+                let fence = &fences[(frame % self.frames_in_flight as u64) as usize];
+                unsafe { device.wait_for_fences(slice::from_ref(&**fence), true, u64::MAX) }?;
+                Ok(())
+            }
+            Mode::Timeline(timeline) => timeline.wait(device, frame + 1),
+        }
+    }
+
+    /// Submit the next frame's `command_buffers` with `submit`'s semaphores, signalling the frame's
+    /// fence or timeline value
+    ///
+    /// # Safety
+    /// As for `Submit::submit`
+    pub unsafe fn submit_frame(
+        &mut self,
+        device: &ash::Device,
+        queue: ash::vk::Queue,
+        submit: Submit,
+        command_buffers: &[ash::vk::CommandBuffer],
+    ) -> Result<()> {
+        let slot = self.slot();
+        match &mut self.mode {
+            Mode::Fences(fences) => {
+                let fence = *fences[slot];
+                // Only reset just before submitting, so a failure before this doesn't leave the
+                //  fence unsignalled with nothing to signal it
+                unsafe { device.reset_fences(slice::from_ref(&fence)) }?;
+                unsafe { submit.submit(device, queue, command_buffers, fence) }?;
+            }
+            Mode::Timeline(timeline) => {
+                let submit = submit.signal(timeline.next_point());
+                unsafe { submit.submit(device, queue, command_buffers, ash::vk::Fence::null()) }?;
+            }
+        }
+
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// The fences or timeline semaphore, to name them
+    pub fn name(&self, names: &DebugUtils) {
+        match &self.mode {
+            Mode::Fences(fences) => names.set_names(&owned::handles(fences), "in flight"),
+            Mode::Timeline(timeline) => names.set_name(timeline.semaphore(), "frame timeline"),
+        }
+    }
+}
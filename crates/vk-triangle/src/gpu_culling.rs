@@ -0,0 +1,419 @@
+use crate::owned::{self, Owned};
+use crate::pod;
+use crate::result::Result;
+use crate::vulkan_app::VulkanApp;
+use shared::{CullObject, CullPushConstants, DrawIndexedIndirectCommand};
+use std::{ffi, ptr, slice};
+
+/// Workgroup size of `cull_cs`
+const CULL_WORKGROUP_SIZE: u32 = 64;
+
+/// GPU-driven drawing: a compute pass frustum culls every object and writes the draws for the
+/// visible ones into an indirect buffer, which is then drawn with a single indirect draw call.
+///
+/// Every buffer is duplicated per frame in flight, so the CPU can write the next frame's objects
+/// while the GPU is still drawing the previous one.
+pub(crate) struct GpuCulling {
+    pub pipeline: Owned<ash::vk::Pipeline>,
+    pub pipeline_layout: Owned<ash::vk::PipelineLayout>,
+    /// Freeing the pool frees `descriptor_sets`
+    pub descriptor_pool: Owned<ash::vk::DescriptorPool>,
+    pub descriptor_sets: Vec<ash::vk::DescriptorSet>,
+    pub descriptor_set_layout: Owned<ash::vk::DescriptorSetLayout>,
+
+    pub max_objects: u32,
+    /// Whether `vkCmdDrawIndexedIndirectCount` can be used, otherwise every slot in the draw
+    /// command buffer is drawn and culled draws are left with no instances
+    pub draw_indirect_count: bool,
+
+    pub cull_object_buffers: Vec<Owned<ash::vk::Buffer>>,
+    pub cull_object_buffers_memory: Vec<Owned<ash::vk::DeviceMemory>>,
+    pub cull_object_buffers_mapped: Vec<*mut ffi::c_void>,
+    pub draw_command_buffers: Vec<Owned<ash::vk::Buffer>>,
+    pub draw_command_buffers_memory: Vec<Owned<ash::vk::DeviceMemory>>,
+    pub draw_count_buffers: Vec<Owned<ash::vk::Buffer>>,
+    pub draw_count_buffers_memory: Vec<Owned<ash::vk::DeviceMemory>>,
+    /// The object index for each draw, read by the vertex shader through `draw_index`
+    pub draw_object_index_buffers: Vec<Owned<ash::vk::Buffer>>,
+    pub draw_object_index_buffers_memory: Vec<Owned<ash::vk::DeviceMemory>>,
+}
+
+impl GpuCulling {
+    pub fn new(
+        instance: &ash::Instance,
+        device: &owned::Device,
+        physical_device: ash::vk::PhysicalDevice,
+        shader_module: ash::vk::ShaderModule,
+        frames_in_flight: u32,
+        max_objects: u32,
+        draw_indirect_count: bool,
+    ) -> Result<Self> {
+        let descriptor_set_layout = Self::create_descriptor_set_layout(device)?;
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(device, shader_module, *descriptor_set_layout)?;
+        let descriptor_pool = Self::create_descriptor_pool(device, frames_in_flight)?;
+
+        let mut culling = Self {
+            pipeline,
+            pipeline_layout,
+            descriptor_pool,
+            descriptor_sets: Vec::new(),
+            descriptor_set_layout,
+            max_objects,
+            draw_indirect_count,
+            cull_object_buffers: Vec::new(),
+            cull_object_buffers_memory: Vec::new(),
+            cull_object_buffers_mapped: Vec::new(),
+            draw_command_buffers: Vec::new(),
+            draw_command_buffers_memory: Vec::new(),
+            draw_count_buffers: Vec::new(),
+            draw_count_buffers_memory: Vec::new(),
+            draw_object_index_buffers: Vec::new(),
+            draw_object_index_buffers_memory: Vec::new(),
+        };
+
+        let max_objects = max_objects as ash::vk::DeviceSize;
+        for _ in 0..frames_in_flight {
+            let cull_objects_size = size_of::<CullObject>() as ash::vk::DeviceSize * max_objects;
+            let (buffer, memory) = VulkanApp::create_buffer(
+                instance,
+                device,
+                cull_objects_size,
+                physical_device,
+                ash::vk::BufferUsageFlags::STORAGE_BUFFER,
+                ash::vk::MemoryPropertyFlags::HOST_COHERENT
+                    | ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )?;
+            let map = unsafe {
+                device.map_memory(
+                    *memory,
+                    0,
+                    cull_objects_size,
+                    ash::vk::MemoryMapFlags::empty(),
+                )
+            }?;
+            culling.cull_object_buffers.push(buffer);
+            culling.cull_object_buffers_memory.push(memory);
+            culling.cull_object_buffers_mapped.push(map);
+
+            let (buffer, memory) = VulkanApp::create_buffer(
+                instance,
+                device,
+                size_of::<DrawIndexedIndirectCommand>() as ash::vk::DeviceSize * max_objects,
+                physical_device,
+                ash::vk::BufferUsageFlags::STORAGE_BUFFER
+                    | ash::vk::BufferUsageFlags::INDIRECT_BUFFER
+                    | ash::vk::BufferUsageFlags::TRANSFER_DST,
+                ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            culling.draw_command_buffers.push(buffer);
+            culling.draw_command_buffers_memory.push(memory);
+
+            let (buffer, memory) = VulkanApp::create_buffer(
+                instance,
+                device,
+                size_of::<u32>() as ash::vk::DeviceSize,
+                physical_device,
+                ash::vk::BufferUsageFlags::STORAGE_BUFFER
+                    | ash::vk::BufferUsageFlags::INDIRECT_BUFFER
+                    | ash::vk::BufferUsageFlags::TRANSFER_DST,
+                ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            culling.draw_count_buffers.push(buffer);
+            culling.draw_count_buffers_memory.push(memory);
+
+            let (buffer, memory) = VulkanApp::create_buffer(
+                instance,
+                device,
+                size_of::<u32>() as ash::vk::DeviceSize * max_objects,
+                physical_device,
+                ash::vk::BufferUsageFlags::STORAGE_BUFFER,
+                ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            culling.draw_object_index_buffers.push(buffer);
+            culling.draw_object_index_buffers_memory.push(memory);
+        }
+
+        culling.create_descriptor_sets(device, frames_in_flight)?;
+
+        Ok(culling)
+    }
+    fn create_descriptor_set_layout(
+        device: &owned::Device,
+    ) -> Result<Owned<ash::vk::DescriptorSetLayout>> {
+        // cull objects, draw commands, draw count, draw object indices
+        let bindings = (0..4)
+            .map(|binding| {
+                ash::vk::DescriptorSetLayoutBinding::default()
+                    .binding(binding)
+                    .descriptor_count(1)
+                    .descriptor_type(ash::vk::DescriptorType::STORAGE_BUFFER)
+                    .stage_flags(ash::vk::ShaderStageFlags::COMPUTE)
+            })
+            .collect::<Vec<_>>();
+
+        let layout_info = ash::vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+        let layout = unsafe { device.create_descriptor_set_layout(&layout_info, None) }?;
+        Ok(unsafe { Owned::new(device, layout) })
+    }
+    fn create_pipeline(
+        device: &owned::Device,
+        shader_module: ash::vk::ShaderModule,
+        descriptor_set_layout: ash::vk::DescriptorSetLayout,
+    ) -> Result<(Owned<ash::vk::PipelineLayout>, Owned<ash::vk::Pipeline>)> {
+        let push_constant_range = ash::vk::PushConstantRange::default()
+            .stage_flags(ash::vk::ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(size_of::<CullPushConstants>() as u32);
+
+        let pipeline_layout_info = ash::vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(slice::from_ref(&descriptor_set_layout))
+            .push_constant_ranges(slice::from_ref(&push_constant_range));
+        let pipeline_layout = unsafe {
+            Owned::new(
+                device,
+                device.create_pipeline_layout(&pipeline_layout_info, None)?,
+            )
+        };
+
+        let stage = ash::vk::PipelineShaderStageCreateInfo::default()
+            .stage(ash::vk::ShaderStageFlags::COMPUTE)
+            .module(shader_module)
+            .name(c"cull_cs");
+        let pipeline_info = ash::vk::ComputePipelineCreateInfo::default()
+            .stage(stage)
+            .layout(*pipeline_layout);
+
+        let pipeline = unsafe {
+            device.create_compute_pipelines(
+                ash::vk::PipelineCache::null(),
+                slice::from_ref(&pipeline_info),
+                None,
+            )
+        }
+        .map_err(|(_, e)| e)?;
+
+        Ok((pipeline_layout, unsafe { Owned::new(device, pipeline[0]) }))
+    }
+    fn create_descriptor_pool(
+        device: &owned::Device,
+        frames_in_flight: u32,
+    ) -> Result<Owned<ash::vk::DescriptorPool>> {
+        let pool_sizes = [ash::vk::DescriptorPoolSize::default()
+            .ty(ash::vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(4 * frames_in_flight)];
+        let pool_info = ash::vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(frames_in_flight);
+        let pool = unsafe { device.create_descriptor_pool(&pool_info, None) }?;
+        Ok(unsafe { Owned::new(device, pool) })
+    }
+    fn create_descriptor_sets(
+        &mut self,
+        device: &ash::Device,
+        frames_in_flight: u32,
+    ) -> Result<()> {
+        let layouts = vec![*self.descriptor_set_layout; frames_in_flight as usize];
+        let alloc_info = ash::vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(*self.descriptor_pool)
+            .set_layouts(&layouts);
+        self.descriptor_sets = unsafe { device.allocate_descriptor_sets(&alloc_info) }?;
+
+        for (frame, &descriptor_set) in self.descriptor_sets.iter().enumerate() {
+            let buffer_infos = [
+                &self.cull_object_buffers[frame],
+                &self.draw_command_buffers[frame],
+                &self.draw_count_buffers[frame],
+                &self.draw_object_index_buffers[frame],
+            ]
+            .map(|buffer| {
+                ash::vk::DescriptorBufferInfo::default()
+                    .buffer(**buffer)
+                    .offset(0)
+                    .range(ash::vk::WHOLE_SIZE)
+            });
+
+            let descriptor_writes = buffer_infos
+                .iter()
+                .zip(0..)
+                .map(|(buffer_info, binding)| {
+                    ash::vk::WriteDescriptorSet::default()
+                        .dst_set(descriptor_set)
+                        .dst_binding(binding)
+                        .dst_array_element(0)
+                        .descriptor_type(ash::vk::DescriptorType::STORAGE_BUFFER)
+                        .buffer_info(slice::from_ref(buffer_info))
+                })
+                .collect::<Vec<_>>();
+            unsafe { device.update_descriptor_sets(&descriptor_writes, &[]) }
+        }
+
+        Ok(())
+    }
+
+    /// Write the objects to be culled in `frame`, returning how many were written
+    pub fn update_objects(
+        &self,
+        frame: usize,
+        objects: impl ExactSizeIterator<Item = CullObject>,
+    ) -> Result<u32> {
+        if objects.len() > self.max_objects as usize {
+            return crate::result::error(&format!(
+                "Can't cull {} objects, at most {} are supported",
+                objects.len(),
+                self.max_objects
+            ));
+        }
+
+        let map = self.cull_object_buffers_mapped[frame] as *mut CullObject;
+        let mut count = 0;
+        for (i, object) in objects.enumerate() {
+            // Safety: the buffer holds `max_objects` entries, and we've checked this fits
+            unsafe { ptr::write_unaligned(map.add(i), object) };
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Record the culling pass for `frame`, leaving the draws ready for `cmd_draw`.
+    ///
+    /// Must be recorded outside a render pass.
+    pub fn cmd_cull(
+        &self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+        push_constants: &CullPushConstants,
+    ) {
+        unsafe {
+            device.cmd_fill_buffer(
+                command_buffer,
+                *self.draw_count_buffers[frame],
+                0,
+                ash::vk::WHOLE_SIZE,
+                0,
+            );
+            if !self.draw_indirect_count {
+                // Without a draw count every slot is drawn, so slots past the visible draws need
+                //  to have no instances
+                device.cmd_fill_buffer(
+                    command_buffer,
+                    *self.draw_command_buffers[frame],
+                    0,
+                    ash::vk::WHOLE_SIZE,
+                    0,
+                );
+            }
+
+            let clear_barrier = ash::vk::MemoryBarrier::default()
+                .src_access_mask(ash::vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(
+                    ash::vk::AccessFlags::SHADER_READ | ash::vk::AccessFlags::SHADER_WRITE,
+                );
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                ash::vk::PipelineStageFlags::TRANSFER,
+                ash::vk::PipelineStageFlags::COMPUTE_SHADER,
+                ash::vk::DependencyFlags::empty(),
+                slice::from_ref(&clear_barrier),
+                &[],
+                &[],
+            );
+
+            device.cmd_bind_pipeline(
+                command_buffer,
+                ash::vk::PipelineBindPoint::COMPUTE,
+                *self.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                ash::vk::PipelineBindPoint::COMPUTE,
+                *self.pipeline_layout,
+                0,
+                slice::from_ref(&self.descriptor_sets[frame]),
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                *self.pipeline_layout,
+                ash::vk::ShaderStageFlags::COMPUTE,
+                0,
+                pod::bytes_of(push_constants),
+            );
+            device.cmd_dispatch(
+                command_buffer,
+                push_constants.object_count.div_ceil(CULL_WORKGROUP_SIZE),
+                1,
+                1,
+            );
+
+            let draw_barrier = ash::vk::MemoryBarrier::default()
+                .src_access_mask(ash::vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(
+                    ash::vk::AccessFlags::INDIRECT_COMMAND_READ | ash::vk::AccessFlags::SHADER_READ,
+                );
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                ash::vk::PipelineStageFlags::COMPUTE_SHADER,
+                ash::vk::PipelineStageFlags::DRAW_INDIRECT
+                    | ash::vk::PipelineStageFlags::VERTEX_SHADER,
+                ash::vk::DependencyFlags::empty(),
+                slice::from_ref(&draw_barrier),
+                &[],
+                &[],
+            );
+        }
+    }
+
+    /// Draw everything that survived the culling pass for `frame`.
+    ///
+    /// `object_count` is the number of objects that were culled.
+    pub fn cmd_draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+        object_count: u32,
+    ) {
+        let stride = size_of::<DrawIndexedIndirectCommand>() as u32;
+        unsafe {
+            if self.draw_indirect_count {
+                device.cmd_draw_indexed_indirect_count(
+                    command_buffer,
+                    *self.draw_command_buffers[frame],
+                    0,
+                    *self.draw_count_buffers[frame],
+                    0,
+                    object_count,
+                    stride,
+                );
+            } else {
+                device.cmd_draw_indexed_indirect(
+                    command_buffer,
+                    *self.draw_command_buffers[frame],
+                    0,
+                    object_count,
+                    stride,
+                );
+            }
+        }
+    }
+}
+
+/// Extract the planes of the frustum from a combined view and projection matrix.
+///
+/// Assumes Vulkan's `0..1` depth range. Plane normals point into the frustum.
+pub(crate) fn frustum_planes(view_projection: glam::Mat4) -> [glam::Vec4; 6] {
+    let row = |i| view_projection.row(i);
+
+    [
+        row(3) + row(0),
+        row(3) - row(0),
+        row(3) + row(1),
+        row(3) - row(1),
+        row(2),
+        row(3) - row(2),
+    ]
+    .map(|plane| plane / plane.truncate().length())
+}
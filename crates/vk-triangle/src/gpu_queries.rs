@@ -0,0 +1,244 @@
+use crate::logging::target;
+use crate::owned::{self, Owned};
+use crate::result::Result;
+
+/// Counters from a pipeline statistics query, in the order of `GpuQueries::STATISTICS`
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PipelineStatistics {
+    pub input_assembly_vertices: u64,
+    pub input_assembly_primitives: u64,
+    pub vertex_shader_invocations: u64,
+    /// Primitives processed by the clipping stage
+    pub clipping_invocations: u64,
+    /// Primitives output by the clipping stage
+    pub clipping_primitives: u64,
+    pub fragment_shader_invocations: u64,
+    pub compute_shader_invocations: u64,
+}
+
+/// Pipeline statistics for each whole frame, and occlusion queries for each directly drawn object.
+///
+/// Like `GpuTimer`, each frame in flight has its own queries, read back once its fence has been
+///  waited on.
+pub(crate) struct GpuQueries {
+    /// `None` if the `pipelineStatisticsQuery` feature isn't supported
+    statistics_pool: Option<Owned<ash::vk::QueryPool>>,
+    occlusion_pool: Owned<ash::vk::QueryPool>,
+    max_objects: u32,
+    frames: Vec<FrameQueries>,
+
+    statistics: Option<PipelineStatistics>,
+    occlusion: Vec<u64>,
+}
+
+#[derive(Default)]
+struct FrameQueries {
+    /// Whether the statistics query was submitted and not yet read
+    statistics_written: bool,
+    /// Number of objects with occlusion queries submitted and not yet read
+    occlusion_count: u32,
+}
+
+impl GpuQueries {
+    /// The counters collected, whose results are written in order of their bits
+    const STATISTICS: ash::vk::QueryPipelineStatisticFlags =
+        ash::vk::QueryPipelineStatisticFlags::from_raw(
+            ash::vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES.as_raw()
+                | ash::vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES.as_raw()
+                | ash::vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS.as_raw()
+                | ash::vk::QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS.as_raw()
+                | ash::vk::QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES.as_raw()
+                | ash::vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS.as_raw()
+                | ash::vk::QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS.as_raw(),
+        );
+    const STATISTICS_COUNT: usize = 7;
+
+    pub fn new(
+        device: &owned::Device,
+        pipeline_statistics_query: bool,
+        frames_in_flight: u32,
+        max_objects: u32,
+    ) -> Result<Self> {
+        let statistics_pool = if pipeline_statistics_query {
+            let pool_info = ash::vk::QueryPoolCreateInfo::default()
+                .query_type(ash::vk::QueryType::PIPELINE_STATISTICS)
+                .pipeline_statistics(Self::STATISTICS)
+                .query_count(frames_in_flight);
+            Some(unsafe { Owned::new(device, device.create_query_pool(&pool_info, None)?) })
+        } else {
+            log::info!(
+                target: target::DEVICE,
+                "pipelineStatisticsQuery isn't supported, so pipeline statistics are disabled"
+            );
+            None
+        };
+
+        let pool_info = ash::vk::QueryPoolCreateInfo::default()
+            .query_type(ash::vk::QueryType::OCCLUSION)
+            .query_count(max_objects * frames_in_flight);
+        let occlusion_pool =
+            unsafe { Owned::new(device, device.create_query_pool(&pool_info, None)?) };
+
+        Ok(Self {
+            statistics_pool,
+            occlusion_pool,
+            max_objects,
+            frames: (0..frames_in_flight)
+                .map(|_| FrameQueries::default())
+                .collect(),
+            statistics: None,
+            occlusion: Vec::new(),
+        })
+    }
+
+    /// Reset `frame`'s queries and start collecting pipeline statistics.
+    ///
+    /// Must be recorded outside of a render pass, and followed by `cmd_end_frame`.
+    pub fn cmd_begin_frame(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+    ) {
+        let queries = &mut self.frames[frame];
+        queries.occlusion_count = 0;
+        unsafe {
+            device.cmd_reset_query_pool(
+                command_buffer,
+                *self.occlusion_pool,
+                self.max_objects * frame as u32,
+                self.max_objects,
+            )
+        };
+
+        if let Some(statistics_pool) = self.statistics_pool.as_deref().copied() {
+            queries.statistics_written = true;
+            unsafe {
+                device.cmd_reset_query_pool(command_buffer, statistics_pool, frame as u32, 1);
+                device.cmd_begin_query(
+                    command_buffer,
+                    statistics_pool,
+                    frame as u32,
+                    ash::vk::QueryControlFlags::empty(),
+                );
+            }
+        }
+    }
+
+    /// Stop collecting pipeline statistics, outside of a render pass
+    pub fn cmd_end_frame(
+        &self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+    ) {
+        if let Some(statistics_pool) = self.statistics_pool.as_deref().copied() {
+            unsafe { device.cmd_end_query(command_buffer, statistics_pool, frame as u32) };
+        }
+    }
+
+    /// Count the samples drawn for scene object `object_index`, until `cmd_end_occlusion`.
+    ///
+    /// Objects must be queried in order from 0, within one subpass.
+    pub fn cmd_begin_occlusion(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+        object_index: u32,
+    ) {
+        if object_index >= self.max_objects {
+            return;
+        }
+        self.frames[frame].occlusion_count = object_index + 1;
+        unsafe {
+            device.cmd_begin_query(
+                command_buffer,
+                *self.occlusion_pool,
+                self.max_objects * frame as u32 + object_index,
+                ash::vk::QueryControlFlags::empty(),
+            )
+        };
+    }
+
+    pub fn cmd_end_occlusion(
+        &self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+        object_index: u32,
+    ) {
+        if object_index >= self.max_objects {
+            return;
+        }
+        unsafe {
+            device.cmd_end_query(
+                command_buffer,
+                *self.occlusion_pool,
+                self.max_objects * frame as u32 + object_index,
+            )
+        };
+    }
+
+    /// Read `frame`'s results into `statistics` and `occlusion`, if they haven't been read yet.
+    ///
+    /// # Safety
+    /// The command buffer the queries were recorded into must have finished executing, e.g.
+    ///  by waiting on the frame's fence.
+    pub unsafe fn read_results(&mut self, device: &ash::Device, frame: usize) -> Result<()> {
+        let queries = &mut self.frames[frame];
+
+        if let (Some(&statistics_pool), true) =
+            (self.statistics_pool.as_deref(), queries.statistics_written)
+        {
+            queries.statistics_written = false;
+            let mut counters = [[0u64; Self::STATISTICS_COUNT]];
+            unsafe {
+                device.get_query_pool_results(
+                    statistics_pool,
+                    frame as u32,
+                    &mut counters,
+                    ash::vk::QueryResultFlags::TYPE_64 | ash::vk::QueryResultFlags::WAIT,
+                )
+            }?;
+            let [c] = counters;
+            self.statistics = Some(PipelineStatistics {
+                input_assembly_vertices: c[0],
+                input_assembly_primitives: c[1],
+                vertex_shader_invocations: c[2],
+                clipping_invocations: c[3],
+                clipping_primitives: c[4],
+                fragment_shader_invocations: c[5],
+                compute_shader_invocations: c[6],
+            });
+        }
+
+        // Frames drawn on the GPU don't have per-object queries, so leave the last results alone
+        let occlusion_count = std::mem::take(&mut queries.occlusion_count);
+        if occlusion_count > 0 {
+            self.occlusion.resize(occlusion_count as usize, 0);
+            unsafe {
+                device.get_query_pool_results(
+                    *self.occlusion_pool,
+                    self.max_objects * frame as u32,
+                    &mut self.occlusion,
+                    ash::vk::QueryResultFlags::TYPE_64 | ash::vk::QueryResultFlags::WAIT,
+                )
+            }?;
+        }
+
+        Ok(())
+    }
+
+    /// Statistics from the most recently read frame
+    pub fn statistics(&self) -> Option<PipelineStatistics> {
+        self.statistics
+    }
+
+    /// Samples that passed for each scene object in the most recently read frame drawn directly.
+    ///
+    /// Without `occlusionQueryPrecise` these are only guaranteed to be non-zero when visible.
+    pub fn occlusion(&self) -> &[u64] {
+        &self.occlusion
+    }
+}
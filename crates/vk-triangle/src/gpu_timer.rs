@@ -0,0 +1,186 @@
+use crate::logging::target;
+use crate::owned::{self, Owned};
+use crate::result::Result;
+use std::time::Duration;
+
+/// Times regions of each frame's command buffer on the GPU with timestamp queries.
+///
+/// Each frame in flight has its own range of queries, which are read back when that frame's
+///  fence is next waited on, so `frames_in_flight` frames after they were written and without
+///  stalling.
+pub(crate) struct GpuTimer {
+    query_pool: Owned<ash::vk::QueryPool>,
+    /// Nanoseconds per timestamp tick
+    timestamp_period: f64,
+    /// Timestamps only have this many meaningful low bits, and wrap around past them
+    valid_bits_mask: u64,
+    frames: Vec<FrameQueries>,
+}
+
+/// The regions written into one frame's queries
+#[derive(Default)]
+struct FrameQueries {
+    /// Each region uses the queries at `2 * index` and `2 * index + 1` for its start and end
+    regions: Vec<&'static str>,
+    /// Regions started but not yet ended, as indices into `regions`
+    open: Vec<usize>,
+    /// Whether the queries have been submitted and not yet read
+    written: bool,
+}
+
+impl GpuTimer {
+    /// The most regions that can be timed in one frame
+    const MAX_REGIONS: u32 = 16;
+
+    /// Create a timer, or `None` if the graphics queue doesn't support timestamps
+    pub fn new(
+        device: &owned::Device,
+        limits: &ash::vk::PhysicalDeviceLimits,
+        queue_family: &ash::vk::QueueFamilyProperties,
+        frames_in_flight: u32,
+    ) -> Result<Option<Self>> {
+        let valid_bits = queue_family.timestamp_valid_bits;
+        if valid_bits == 0 {
+            log::info!(
+                target: target::DEVICE,
+                "The graphics queue doesn't support timestamps, so GPU timings are disabled"
+            );
+            return Ok(None);
+        }
+
+        let pool_info = ash::vk::QueryPoolCreateInfo::default()
+            .query_type(ash::vk::QueryType::TIMESTAMP)
+            .query_count(2 * Self::MAX_REGIONS * frames_in_flight);
+        let query_pool = unsafe { Owned::new(device, device.create_query_pool(&pool_info, None)?) };
+
+        Ok(Some(Self {
+            query_pool,
+            timestamp_period: limits.timestamp_period as f64,
+            valid_bits_mask: u64::MAX >> (64 - valid_bits.min(64)),
+            frames: (0..frames_in_flight)
+                .map(|_| FrameQueries::default())
+                .collect(),
+        }))
+    }
+
+    fn first_query(frame: usize) -> u32 {
+        2 * Self::MAX_REGIONS * frame as u32
+    }
+
+    /// Reset `frame`'s queries, before recording any regions into its command buffer.
+    ///
+    /// Must be recorded outside of a render pass.
+    pub fn cmd_begin_frame(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+    ) {
+        let queries = &mut self.frames[frame];
+        queries.regions.clear();
+        queries.open.clear();
+        queries.written = true;
+
+        unsafe {
+            device.cmd_reset_query_pool(
+                command_buffer,
+                *self.query_pool,
+                Self::first_query(frame),
+                2 * Self::MAX_REGIONS,
+            )
+        };
+    }
+
+    /// Start timing a region called `name`, ended by the next unmatched `cmd_end`.
+    ///
+    /// Regions past `MAX_REGIONS` in a frame are ignored.
+    pub fn cmd_begin(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+        name: &'static str,
+    ) {
+        let queries = &mut self.frames[frame];
+        if queries.regions.len() as u32 >= Self::MAX_REGIONS {
+            // Still track it so the matching `cmd_end` is ignored too
+            queries.open.push(usize::MAX);
+            return;
+        }
+
+        let index = queries.regions.len();
+        queries.regions.push(name);
+        queries.open.push(index);
+        unsafe {
+            device.cmd_write_timestamp(
+                command_buffer,
+                ash::vk::PipelineStageFlags::TOP_OF_PIPE,
+                *self.query_pool,
+                Self::first_query(frame) + 2 * index as u32,
+            )
+        };
+    }
+
+    /// End the most recently started region
+    pub fn cmd_end(
+        &mut self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        frame: usize,
+    ) {
+        let Some(index) = self.frames[frame].open.pop() else {
+            return;
+        };
+        if index == usize::MAX {
+            return;
+        }
+        unsafe {
+            device.cmd_write_timestamp(
+                command_buffer,
+                ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                *self.query_pool,
+                Self::first_query(frame) + 2 * index as u32 + 1,
+            )
+        };
+    }
+
+    /// Read the region timings last recorded for `frame`, or nothing if they've already been read.
+    ///
+    /// # Safety
+    /// The command buffer the queries were recorded into must have finished executing, e.g.
+    ///  by waiting on the frame's fence.
+    pub unsafe fn read_results(
+        &mut self,
+        device: &ash::Device,
+        frame: usize,
+    ) -> Result<Vec<(&'static str, Duration)>> {
+        let queries = &mut self.frames[frame];
+        if !queries.written || queries.regions.is_empty() {
+            return Ok(Vec::new());
+        }
+        queries.written = false;
+
+        let mut timestamps = vec![0u64; 2 * queries.regions.len()];
+        unsafe {
+            device.get_query_pool_results(
+                *self.query_pool,
+                Self::first_query(frame),
+                &mut timestamps,
+                ash::vk::QueryResultFlags::TYPE_64 | ash::vk::QueryResultFlags::WAIT,
+            )
+        }?;
+
+        let results = queries
+            .regions
+            .iter()
+            .zip(timestamps.chunks_exact(2))
+            .map(|(&name, timestamps)| {
+                let (start, end) = (timestamps[0], timestamps[1]);
+                let ticks = end.wrapping_sub(start) & self.valid_bits_mask;
+                let nanos = ticks as f64 * self.timestamp_period;
+                (name, Duration::from_nanos(nanos as u64))
+            })
+            .collect();
+        Ok(results)
+    }
+}
@@ -0,0 +1,346 @@
+use crate::result::{err, Result};
+use std::slice;
+
+/// A single image layout transition, recorded into an existing command buffer with
+/// [`cmd_transition_image_layout`] or [`cmd_transition_image_layout2`].
+///
+/// Stage and access masks are derived from the old and new layouts, so only the layouts and
+/// the affected subresources need describing.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ImageTransition {
+    pub image: ash::vk::Image,
+    pub old_layout: ash::vk::ImageLayout,
+    pub new_layout: ash::vk::ImageLayout,
+    pub subresource_range: ash::vk::ImageSubresourceRange,
+    pub queue_transfer: Option<QueueTransfer>,
+}
+
+/// Which half of a queue family ownership transfer a barrier is.
+///
+/// The same transition must be recorded twice: as a `Release` on a queue from `src_family`, then
+/// as an `Acquire` on a queue from `dst_family`, with a semaphore between the two submissions.
+// Nothing uses a dedicated transfer or compute queue yet
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QueueTransfer {
+    Release { src_family: u32, dst_family: u32 },
+    Acquire { src_family: u32, dst_family: u32 },
+}
+
+impl ImageTransition {
+    /// Transition the first mip level and array layer of `image`, using the aspects implied by
+    /// `format`
+    pub fn new(
+        image: ash::vk::Image,
+        format: ash::vk::Format,
+        old_layout: ash::vk::ImageLayout,
+        new_layout: ash::vk::ImageLayout,
+    ) -> Self {
+        Self {
+            image,
+            old_layout,
+            new_layout,
+            subresource_range: ash::vk::ImageSubresourceRange::default()
+                .aspect_mask(aspect_mask_for_format(format))
+                .base_mip_level(0)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(1),
+            queue_transfer: None,
+        }
+    }
+    /// `level_count` may be `ash::vk::REMAINING_MIP_LEVELS`
+    pub fn mip_levels(mut self, base_mip_level: u32, level_count: u32) -> Self {
+        self.subresource_range.base_mip_level = base_mip_level;
+        self.subresource_range.level_count = level_count;
+        self
+    }
+    /// `layer_count` may be `ash::vk::REMAINING_ARRAY_LAYERS`
+    pub fn array_layers(mut self, base_array_layer: u32, layer_count: u32) -> Self {
+        self.subresource_range.base_array_layer = base_array_layer;
+        self.subresource_range.layer_count = layer_count;
+        self
+    }
+    #[allow(dead_code)]
+    pub fn queue_transfer(mut self, queue_transfer: QueueTransfer) -> Self {
+        self.queue_transfer = Some(queue_transfer);
+        self
+    }
+
+    fn queue_family_indices(&self) -> (u32, u32) {
+        match self.queue_transfer {
+            None => (ash::vk::QUEUE_FAMILY_IGNORED, ash::vk::QUEUE_FAMILY_IGNORED),
+            Some(QueueTransfer::Release {
+                src_family,
+                dst_family,
+            })
+            | Some(QueueTransfer::Acquire {
+                src_family,
+                dst_family,
+            }) => (src_family, dst_family),
+        }
+    }
+    fn is_release(&self) -> bool {
+        matches!(self.queue_transfer, Some(QueueTransfer::Release { .. }))
+    }
+    fn is_acquire(&self) -> bool {
+        matches!(self.queue_transfer, Some(QueueTransfer::Acquire { .. }))
+    }
+}
+
+/// The aspects an image of `format` has, for use in subresource ranges
+pub(crate) fn aspect_mask_for_format(format: ash::vk::Format) -> ash::vk::ImageAspectFlags {
+    match format {
+        ash::vk::Format::D16_UNORM
+        | ash::vk::Format::X8_D24_UNORM_PACK32
+        | ash::vk::Format::D32_SFLOAT => ash::vk::ImageAspectFlags::DEPTH,
+        ash::vk::Format::S8_UINT => ash::vk::ImageAspectFlags::STENCIL,
+        ash::vk::Format::D16_UNORM_S8_UINT
+        | ash::vk::Format::D24_UNORM_S8_UINT
+        | ash::vk::Format::D32_SFLOAT_S8_UINT => {
+            ash::vk::ImageAspectFlags::DEPTH | ash::vk::ImageAspectFlags::STENCIL
+        }
+        _ => ash::vk::ImageAspectFlags::COLOR,
+    }
+}
+
+/// The stages that may touch an image in `layout`, and the accesses they may make.
+///
+/// Returns `None` for layouts that an image can't be transitioned into, or that we don't know how
+/// to synchronise.
+fn layout_usage(
+    layout: ash::vk::ImageLayout,
+) -> Option<(ash::vk::PipelineStageFlags, ash::vk::AccessFlags)> {
+    use ash::vk::{AccessFlags as A, ImageLayout as L, PipelineStageFlags as S};
+
+    let usage = match layout {
+        L::UNDEFINED => (S::TOP_OF_PIPE, A::empty()),
+        L::PREINITIALIZED => (S::HOST, A::HOST_WRITE),
+        L::GENERAL => (S::ALL_COMMANDS, A::MEMORY_READ | A::MEMORY_WRITE),
+        L::COLOR_ATTACHMENT_OPTIMAL => (
+            S::COLOR_ATTACHMENT_OUTPUT,
+            A::COLOR_ATTACHMENT_READ | A::COLOR_ATTACHMENT_WRITE,
+        ),
+        L::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        | L::DEPTH_ATTACHMENT_OPTIMAL
+        | L::STENCIL_ATTACHMENT_OPTIMAL
+        | L::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL
+        | L::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL => (
+            S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS,
+            A::DEPTH_STENCIL_ATTACHMENT_READ | A::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        L::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        | L::DEPTH_READ_ONLY_OPTIMAL
+        | L::STENCIL_READ_ONLY_OPTIMAL => (
+            S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS | S::FRAGMENT_SHADER,
+            A::DEPTH_STENCIL_ATTACHMENT_READ | A::SHADER_READ,
+        ),
+        L::SHADER_READ_ONLY_OPTIMAL => (
+            S::VERTEX_SHADER | S::FRAGMENT_SHADER | S::COMPUTE_SHADER,
+            A::SHADER_READ,
+        ),
+        L::TRANSFER_SRC_OPTIMAL => (S::TRANSFER, A::TRANSFER_READ),
+        L::TRANSFER_DST_OPTIMAL => (S::TRANSFER, A::TRANSFER_WRITE),
+        // Presentation is synchronised with semaphores, the barrier only needs to order the
+        //  layout transition against the colour attachment writes
+        L::PRESENT_SRC_KHR => (S::COLOR_ATTACHMENT_OUTPUT, A::empty()),
+        _ => return None,
+    };
+
+    Some(usage)
+}
+
+/// As `layout_usage`, but with the finer-grained stages and accesses of `synchronization2`
+fn layout_usage2(
+    layout: ash::vk::ImageLayout,
+) -> Option<(ash::vk::PipelineStageFlags2, ash::vk::AccessFlags2)> {
+    use ash::vk::{AccessFlags2 as A, ImageLayout as L, PipelineStageFlags2 as S};
+
+    let usage = match layout {
+        L::UNDEFINED => (S::NONE, A::NONE),
+        L::PREINITIALIZED => (S::HOST, A::HOST_WRITE),
+        L::GENERAL => (S::ALL_COMMANDS, A::MEMORY_READ | A::MEMORY_WRITE),
+        L::COLOR_ATTACHMENT_OPTIMAL => (
+            S::COLOR_ATTACHMENT_OUTPUT,
+            A::COLOR_ATTACHMENT_READ | A::COLOR_ATTACHMENT_WRITE,
+        ),
+        L::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        | L::DEPTH_ATTACHMENT_OPTIMAL
+        | L::STENCIL_ATTACHMENT_OPTIMAL
+        | L::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL
+        | L::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL => (
+            S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS,
+            A::DEPTH_STENCIL_ATTACHMENT_READ | A::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        L::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        | L::DEPTH_READ_ONLY_OPTIMAL
+        | L::STENCIL_READ_ONLY_OPTIMAL => (
+            S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS | S::FRAGMENT_SHADER,
+            A::DEPTH_STENCIL_ATTACHMENT_READ | A::SHADER_SAMPLED_READ,
+        ),
+        L::SHADER_READ_ONLY_OPTIMAL => (
+            S::VERTEX_SHADER | S::FRAGMENT_SHADER | S::COMPUTE_SHADER,
+            A::SHADER_SAMPLED_READ,
+        ),
+        L::READ_ONLY_OPTIMAL => (
+            S::ALL_GRAPHICS | S::COMPUTE_SHADER,
+            A::SHADER_SAMPLED_READ | A::DEPTH_STENCIL_ATTACHMENT_READ,
+        ),
+        L::ATTACHMENT_OPTIMAL => (
+            S::COLOR_ATTACHMENT_OUTPUT | S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS,
+            A::COLOR_ATTACHMENT_READ
+                | A::COLOR_ATTACHMENT_WRITE
+                | A::DEPTH_STENCIL_ATTACHMENT_READ
+                | A::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        L::TRANSFER_SRC_OPTIMAL => (S::COPY | S::BLIT | S::RESOLVE, A::TRANSFER_READ),
+        L::TRANSFER_DST_OPTIMAL => (S::COPY | S::BLIT | S::RESOLVE | S::CLEAR, A::TRANSFER_WRITE),
+        L::PRESENT_SRC_KHR => (S::COLOR_ATTACHMENT_OUTPUT, A::NONE),
+        _ => return None,
+    };
+
+    Some(usage)
+}
+
+const WRITE_ACCESS: ash::vk::AccessFlags = ash::vk::AccessFlags::from_raw(
+    ash::vk::AccessFlags::SHADER_WRITE.as_raw()
+        | ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE.as_raw()
+        | ash::vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+        | ash::vk::AccessFlags::TRANSFER_WRITE.as_raw()
+        | ash::vk::AccessFlags::HOST_WRITE.as_raw()
+        | ash::vk::AccessFlags::MEMORY_WRITE.as_raw(),
+);
+const WRITE_ACCESS2: ash::vk::AccessFlags2 = ash::vk::AccessFlags2::from_raw(
+    ash::vk::AccessFlags2::SHADER_WRITE.as_raw()
+        | ash::vk::AccessFlags2::SHADER_STORAGE_WRITE.as_raw()
+        | ash::vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.as_raw()
+        | ash::vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+        | ash::vk::AccessFlags2::TRANSFER_WRITE.as_raw()
+        | ash::vk::AccessFlags2::HOST_WRITE.as_raw()
+        | ash::vk::AccessFlags2::MEMORY_WRITE.as_raw(),
+);
+
+fn unsupported(transition: &ImageTransition) -> Box<dyn core::error::Error> {
+    err(&format!(
+        "Unsupported layout transition: {:?} -> {:?}",
+        transition.old_layout, transition.new_layout
+    ))
+    .into()
+}
+
+/// Record `transition` into `command_buffer` with `vkCmdPipelineBarrier`
+pub(crate) fn cmd_transition_image_layout(
+    device: &ash::Device,
+    command_buffer: ash::vk::CommandBuffer,
+    transition: &ImageTransition,
+) -> Result<()> {
+    if transition.new_layout == ash::vk::ImageLayout::UNDEFINED
+        || transition.new_layout == ash::vk::ImageLayout::PREINITIALIZED
+    {
+        return Err(unsupported(transition));
+    }
+    let (src_stage, src_access) =
+        layout_usage(transition.old_layout).ok_or_else(|| unsupported(transition))?;
+    let (dst_stage, dst_access) =
+        layout_usage(transition.new_layout).ok_or_else(|| unsupported(transition))?;
+
+    // Only writes need to be made available; the releasing half of an ownership transfer
+    //  has no destination, and the acquiring half no source
+    let (src_stage, src_access) = if transition.is_acquire() {
+        (
+            ash::vk::PipelineStageFlags::TOP_OF_PIPE,
+            ash::vk::AccessFlags::empty(),
+        )
+    } else {
+        (src_stage, src_access & WRITE_ACCESS)
+    };
+    let (dst_stage, dst_access) = if transition.is_release() {
+        (
+            ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            ash::vk::AccessFlags::empty(),
+        )
+    } else {
+        (dst_stage, dst_access)
+    };
+
+    let (src_queue_family_index, dst_queue_family_index) = transition.queue_family_indices();
+    let barrier = ash::vk::ImageMemoryBarrier::default()
+        .old_layout(transition.old_layout)
+        .new_layout(transition.new_layout)
+        .src_queue_family_index(src_queue_family_index)
+        .dst_queue_family_index(dst_queue_family_index)
+        .image(transition.image)
+        .subresource_range(transition.subresource_range)
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access);
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage,
+            dst_stage,
+            ash::vk::DependencyFlags::default(),
+            &[],
+            &[],
+            slice::from_ref(&barrier),
+        )
+    };
+
+    Ok(())
+}
+
+/// Record `transition` into `command_buffer` with `vkCmdPipelineBarrier2`.
+///
+/// Requires the `synchronization2` feature to have been enabled on the device.
+pub(crate) fn cmd_transition_image_layout2(
+    synchronization2_device: &ash::khr::synchronization2::Device,
+    command_buffer: ash::vk::CommandBuffer,
+    transition: &ImageTransition,
+) -> Result<()> {
+    if transition.new_layout == ash::vk::ImageLayout::UNDEFINED
+        || transition.new_layout == ash::vk::ImageLayout::PREINITIALIZED
+    {
+        return Err(unsupported(transition));
+    }
+    let (src_stage, src_access) =
+        layout_usage2(transition.old_layout).ok_or_else(|| unsupported(transition))?;
+    let (dst_stage, dst_access) =
+        layout_usage2(transition.new_layout).ok_or_else(|| unsupported(transition))?;
+
+    let (src_stage, src_access) = if transition.is_acquire() {
+        (
+            ash::vk::PipelineStageFlags2::NONE,
+            ash::vk::AccessFlags2::NONE,
+        )
+    } else {
+        (src_stage, src_access & WRITE_ACCESS2)
+    };
+    let (dst_stage, dst_access) = if transition.is_release() {
+        (
+            ash::vk::PipelineStageFlags2::NONE,
+            ash::vk::AccessFlags2::NONE,
+        )
+    } else {
+        (dst_stage, dst_access)
+    };
+
+    let (src_queue_family_index, dst_queue_family_index) = transition.queue_family_indices();
+    let barrier = ash::vk::ImageMemoryBarrier2::default()
+        .src_stage_mask(src_stage)
+        .src_access_mask(src_access)
+        .dst_stage_mask(dst_stage)
+        .dst_access_mask(dst_access)
+        .old_layout(transition.old_layout)
+        .new_layout(transition.new_layout)
+        .src_queue_family_index(src_queue_family_index)
+        .dst_queue_family_index(dst_queue_family_index)
+        .image(transition.image)
+        .subresource_range(transition.subresource_range);
+
+    let dependency_info =
+        ash::vk::DependencyInfo::default().image_memory_barriers(slice::from_ref(&barrier));
+
+    unsafe { synchronization2_device.cmd_pipeline_barrier2(command_buffer, &dependency_info) };
+
+    Ok(())
+}
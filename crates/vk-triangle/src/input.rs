@@ -0,0 +1,481 @@
+use glam::DVec2;
+use glfw::{Action, GamepadAxis, GamepadButton, Glfw, JoystickId, Key, MouseButton, WindowEvent};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Names of the actions and axes the app looks up, so typos are caught at compile time
+pub(crate) mod bindings {
+    pub const TOGGLE_CAMERA_MODE: &str = "toggle_camera_mode";
+    /// Held to look around with the mouse
+    pub const LOOK: &str = "look";
+    pub const SPRINT: &str = "sprint";
+    pub const CLOSE: &str = "close";
+    pub const CYCLE_PRESENT_MODE: &str = "cycle_present_mode";
+    pub const CYCLE_WINDOW_MODE: &str = "cycle_window_mode";
+    pub const NEXT_MONITOR: &str = "next_monitor";
+    /// Switch between GPU culling with indirect draws, and a draw call per object
+    pub const TOGGLE_GPU_CULLING: &str = "toggle_gpu_culling";
+    /// Print the pipeline statistics and occlusion query results
+    pub const PRINT_GPU_QUERIES: &str = "print_gpu_queries";
+
+    pub const MOVE_FORWARD: &str = "move_forward";
+    pub const MOVE_RIGHT: &str = "move_right";
+    pub const MOVE_UP: &str = "move_up";
+    /// Mouse movement while `LOOK` is held, in pixels
+    pub const LOOK_X: &str = "look_x";
+    pub const LOOK_Y: &str = "look_y";
+    /// Continuous turning, e.g. from a stick, in `-1..=1`
+    pub const TURN_X: &str = "turn_x";
+    pub const TURN_Y: &str = "turn_y";
+    /// Zoom steps this frame, e.g. from the scroll wheel
+    pub const ZOOM: &str = "zoom";
+}
+
+/// Something with an up or down state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) enum ButtonInput {
+    Key(Key),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+/// Something with a continuous value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) enum AnalogInput {
+    /// Cursor movement this frame, in pixels
+    MouseX,
+    MouseY,
+    /// Scroll wheel movement this frame
+    ScrollX,
+    ScrollY,
+    /// Gamepad stick in `-1..=1`, or trigger in `0..=1`
+    Gamepad(GamepadAxis),
+}
+
+/// One source contributing to an axis: `(analog + positive - negative) * scale`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AxisBinding {
+    #[serde(default)]
+    pub positive: Option<ButtonInput>,
+    #[serde(default)]
+    pub negative: Option<ButtonInput>,
+    #[serde(default)]
+    pub analog: Option<AnalogInput>,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+/// The `[input]` section of the config file.
+///
+/// Actions and axes listed in the config replace the built-in bindings with the same name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct InputConfig {
+    pub actions: BTreeMap<String, Vec<ButtonInput>>,
+    pub axes: BTreeMap<String, Vec<AxisBinding>>,
+    /// Gamepad stick values smaller than this are treated as zero
+    pub gamepad_deadzone: f32,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        use bindings::*;
+        use ButtonInput as B;
+
+        let buttons = |positive, negative| AxisBinding {
+            positive: Some(positive),
+            negative: Some(negative),
+            analog: None,
+            scale: 1.0,
+        };
+        let analog = |analog, scale| AxisBinding {
+            positive: None,
+            negative: None,
+            analog: Some(analog),
+            scale,
+        };
+        let stick = |axis, scale| analog(AnalogInput::Gamepad(axis), scale);
+
+        let actions = [
+            (
+                TOGGLE_CAMERA_MODE,
+                vec![B::Key(Key::Tab), B::Gamepad(GamepadButton::ButtonY)],
+            ),
+            (
+                LOOK,
+                vec![B::Mouse(MouseButton::Left), B::Mouse(MouseButton::Right)],
+            ),
+            (
+                SPRINT,
+                vec![
+                    B::Key(Key::LeftShift),
+                    B::Gamepad(GamepadButton::ButtonLeftThumb),
+                ],
+            ),
+            (CLOSE, vec![B::Key(Key::Escape)]),
+            (CYCLE_PRESENT_MODE, vec![B::Key(Key::V)]),
+            (CYCLE_WINDOW_MODE, vec![B::Key(Key::F11)]),
+            (NEXT_MONITOR, vec![B::Key(Key::F10)]),
+            (TOGGLE_GPU_CULLING, vec![B::Key(Key::G)]),
+            (PRINT_GPU_QUERIES, vec![B::Key(Key::P)]),
+        ];
+        let axes = [
+            (
+                MOVE_FORWARD,
+                vec![
+                    buttons(B::Key(Key::W), B::Key(Key::S)),
+                    stick(GamepadAxis::AxisLeftY, -1.0),
+                ],
+            ),
+            (
+                MOVE_RIGHT,
+                vec![
+                    buttons(B::Key(Key::D), B::Key(Key::A)),
+                    stick(GamepadAxis::AxisLeftX, 1.0),
+                ],
+            ),
+            (
+                MOVE_UP,
+                vec![
+                    buttons(B::Key(Key::Space), B::Key(Key::LeftControl)),
+                    buttons(
+                        B::Gamepad(GamepadButton::ButtonRightBumper),
+                        B::Gamepad(GamepadButton::ButtonLeftBumper),
+                    ),
+                ],
+            ),
+            (LOOK_X, vec![analog(AnalogInput::MouseX, 1.0)]),
+            (LOOK_Y, vec![analog(AnalogInput::MouseY, 1.0)]),
+            (TURN_X, vec![stick(GamepadAxis::AxisRightX, 1.0)]),
+            (TURN_Y, vec![stick(GamepadAxis::AxisRightY, 1.0)]),
+            (ZOOM, vec![analog(AnalogInput::ScrollY, 1.0)]),
+        ];
+
+        Self {
+            actions: actions
+                .into_iter()
+                .map(|(name, inputs)| (name.to_string(), inputs))
+                .collect(),
+            axes: axes
+                .into_iter()
+                .map(|(name, bindings)| (name.to_string(), bindings))
+                .collect(),
+            gamepad_deadzone: 0.15,
+        }
+    }
+}
+
+/// Tracks the state of every input, and maps them onto named actions and axes.
+///
+/// Each frame, call `begin_frame`, then pass every window event to `handle_event`, then call
+/// `poll_gamepad`. The rest of the app then queries actions and axes by name.
+pub(crate) struct Input {
+    config: InputConfig,
+
+    /// Buttons down this frame, and last frame
+    down: Vec<ButtonInput>,
+    previously_down: Vec<ButtonInput>,
+
+    cursor_position: Option<DVec2>,
+    cursor_delta: DVec2,
+    scroll_delta: DVec2,
+    gamepad_axes: Vec<(GamepadAxis, f32)>,
+}
+
+impl Input {
+    pub fn new(config: InputConfig) -> Self {
+        let mut merged = InputConfig {
+            gamepad_deadzone: config.gamepad_deadzone,
+            ..InputConfig::default()
+        };
+        merged.actions.extend(config.actions);
+        merged.axes.extend(config.axes);
+
+        Self {
+            config: merged,
+            down: Vec::new(),
+            previously_down: Vec::new(),
+            cursor_position: None,
+            cursor_delta: DVec2::ZERO,
+            scroll_delta: DVec2::ZERO,
+            gamepad_axes: Vec::new(),
+        }
+    }
+
+    /// Start a new frame, before any of this frame's events are handled
+    pub fn begin_frame(&mut self) {
+        self.previously_down.clone_from(&self.down);
+        self.cursor_delta = DVec2::ZERO;
+        self.scroll_delta = DVec2::ZERO;
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Key(key, _, action, _) => self.set_button(ButtonInput::Key(key), action),
+            WindowEvent::MouseButton(button, action, _) => {
+                self.set_button(ButtonInput::Mouse(button), action)
+            }
+            WindowEvent::CursorPos(x, y) => {
+                let position = DVec2::new(x, y);
+                if let Some(previous) = self.cursor_position {
+                    self.cursor_delta += position - previous;
+                }
+                self.cursor_position = Some(position);
+            }
+            WindowEvent::Scroll(x, y) => self.scroll_delta += DVec2::new(x, y),
+            WindowEvent::Focus(false) => {
+                // Release events won't arrive while unfocused, so don't leave anything stuck down
+                self.down.clear();
+                self.cursor_position = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Read the state of the first connected gamepad, if there is one
+    pub fn poll_gamepad(&mut self, glfw: &Glfw) {
+        self.down
+            .retain(|input| !matches!(input, ButtonInput::Gamepad(_)));
+        self.gamepad_axes.clear();
+
+        let joystick = glfw.get_joystick(JoystickId::Joystick1);
+        if !joystick.is_gamepad() {
+            return;
+        }
+        let Some(state) = joystick.get_gamepad_state() else {
+            return;
+        };
+
+        for button in GAMEPAD_BUTTONS.iter().map(|&(_, button)| button) {
+            if state.get_button_state(button) == Action::Press {
+                self.down.push(ButtonInput::Gamepad(button));
+            }
+        }
+        for axis in GAMEPAD_AXES.iter().map(|&(_, axis)| axis) {
+            let value = state.get_axis(axis);
+            let value = match axis {
+                // Triggers rest at -1
+                GamepadAxis::AxisLeftTrigger | GamepadAxis::AxisRightTrigger => (value + 1.0) / 2.0,
+                _ => value,
+            };
+            if value.abs() >= self.config.gamepad_deadzone {
+                self.gamepad_axes.push((axis, value));
+            }
+        }
+    }
+
+    /// Whether the action went down this frame
+    pub fn pressed(&self, action: &str) -> bool {
+        self.held(action) && !self.was_held(action)
+    }
+
+    /// Whether the action is currently down
+    pub fn held(&self, action: &str) -> bool {
+        self.action_inputs(action)
+            .any(|input| self.down.contains(input))
+    }
+
+    /// Whether the action went up this frame
+    #[allow(dead_code)] // Completes the set of action states, but nothing needs it yet
+    pub fn released(&self, action: &str) -> bool {
+        self.was_held(action) && !self.held(action)
+    }
+
+    /// Sum of every binding of the axis, or 0 if it has none
+    pub fn axis(&self, axis: &str) -> f32 {
+        let Some(bindings) = self.config.axes.get(axis) else {
+            return 0.0;
+        };
+
+        bindings
+            .iter()
+            .map(|binding| {
+                let button = |input: Option<ButtonInput>| {
+                    input.is_some_and(|input| self.down.contains(&input)) as i32 as f32
+                };
+                let analog = binding.analog.map_or(0.0, |analog| self.analog(analog));
+                (analog + button(binding.positive) - button(binding.negative)) * binding.scale
+            })
+            .sum()
+    }
+
+    fn was_held(&self, action: &str) -> bool {
+        self.action_inputs(action)
+            .any(|input| self.previously_down.contains(input))
+    }
+
+    fn action_inputs(&self, action: &str) -> impl Iterator<Item = &ButtonInput> {
+        self.config.actions.get(action).into_iter().flatten()
+    }
+
+    fn analog(&self, input: AnalogInput) -> f32 {
+        match input {
+            AnalogInput::MouseX => self.cursor_delta.x as f32,
+            AnalogInput::MouseY => self.cursor_delta.y as f32,
+            AnalogInput::ScrollX => self.scroll_delta.x as f32,
+            AnalogInput::ScrollY => self.scroll_delta.y as f32,
+            AnalogInput::Gamepad(axis) => self
+                .gamepad_axes
+                .iter()
+                .find(|&&(a, _)| a == axis)
+                .map_or(0.0, |&(_, value)| value),
+        }
+    }
+
+    fn set_button(&mut self, input: ButtonInput, action: Action) {
+        match action {
+            Action::Press => {
+                if !self.down.contains(&input) {
+                    self.down.push(input);
+                }
+            }
+            Action::Release => self.down.retain(|&down| down != input),
+            Action::Repeat => {}
+        }
+    }
+}
+
+/// Failed to parse a binding from the config file
+#[derive(Debug)]
+pub(crate) struct ParseInputError(String);
+
+impl Display for ParseInputError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TryFrom<String> for ButtonInput {
+    type Error = ParseInputError;
+
+    /// Parses `key:<name>`, `mouse:<left|right|middle|1-8>` or `gamepad:<button>`
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (device, name) = split_binding(&value)?;
+        let input = match device.as_str() {
+            "key" => find(KEYS, &name).map(ButtonInput::Key),
+            "mouse" => find(MOUSE_BUTTONS, &name).map(ButtonInput::Mouse),
+            "gamepad" => find(GAMEPAD_BUTTONS, &name).map(ButtonInput::Gamepad),
+            _ => None,
+        };
+        input.ok_or_else(|| ParseInputError(format!("Unknown button binding `{value}`")))
+    }
+}
+
+impl TryFrom<String> for AnalogInput {
+    type Error = ParseInputError;
+
+    /// Parses `mouse:<x|y>`, `scroll:<x|y>` or `gamepad:<axis>`
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (device, name) = split_binding(&value)?;
+        let input = match (device.as_str(), name.as_str()) {
+            ("mouse", "x") => Some(AnalogInput::MouseX),
+            ("mouse", "y") => Some(AnalogInput::MouseY),
+            ("scroll", "x") => Some(AnalogInput::ScrollX),
+            ("scroll", "y") => Some(AnalogInput::ScrollY),
+            ("gamepad", name) => find(GAMEPAD_AXES, name).map(AnalogInput::Gamepad),
+            _ => None,
+        };
+        input.ok_or_else(|| ParseInputError(format!("Unknown analog binding `{value}`")))
+    }
+}
+
+/// Split `device:name` into lowercase parts, ignoring underscores in the name
+fn split_binding(value: &str) -> Result<(String, String), ParseInputError> {
+    let (device, name) = value
+        .split_once(':')
+        .ok_or_else(|| ParseInputError(format!("Binding `{value}` should be `device:name`")))?;
+    Ok((
+        device.trim().to_lowercase(),
+        name.trim().to_lowercase().replace('_', ""),
+    ))
+}
+
+fn find<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(table_name, _)| *table_name == name)
+        .map(|&(_, value)| value)
+}
+
+#[rustfmt::skip] // One line per row reads better than rustfmt's layout
+const KEYS: &[(&str, Key)] = &[
+    ("space", Key::Space), ("apostrophe", Key::Apostrophe), ("comma", Key::Comma),
+    ("minus", Key::Minus), ("period", Key::Period), ("slash", Key::Slash),
+    ("0", Key::Num0), ("1", Key::Num1), ("2", Key::Num2), ("3", Key::Num3), ("4", Key::Num4),
+    ("5", Key::Num5), ("6", Key::Num6), ("7", Key::Num7), ("8", Key::Num8), ("9", Key::Num9),
+    ("semicolon", Key::Semicolon), ("equal", Key::Equal),
+    ("a", Key::A), ("b", Key::B), ("c", Key::C), ("d", Key::D), ("e", Key::E), ("f", Key::F),
+    ("g", Key::G), ("h", Key::H), ("i", Key::I), ("j", Key::J), ("k", Key::K), ("l", Key::L),
+    ("m", Key::M), ("n", Key::N), ("o", Key::O), ("p", Key::P), ("q", Key::Q), ("r", Key::R),
+    ("s", Key::S), ("t", Key::T), ("u", Key::U), ("v", Key::V), ("w", Key::W), ("x", Key::X),
+    ("y", Key::Y), ("z", Key::Z),
+    ("leftbracket", Key::LeftBracket), ("backslash", Key::Backslash),
+    ("rightbracket", Key::RightBracket), ("graveaccent", Key::GraveAccent),
+    ("escape", Key::Escape), ("enter", Key::Enter), ("tab", Key::Tab),
+    ("backspace", Key::Backspace), ("insert", Key::Insert), ("delete", Key::Delete),
+    ("right", Key::Right), ("left", Key::Left), ("down", Key::Down), ("up", Key::Up),
+    ("pageup", Key::PageUp), ("pagedown", Key::PageDown), ("home", Key::Home), ("end", Key::End),
+    ("capslock", Key::CapsLock), ("scrolllock", Key::ScrollLock), ("numlock", Key::NumLock),
+    ("printscreen", Key::PrintScreen), ("pause", Key::Pause),
+    ("f1", Key::F1), ("f2", Key::F2), ("f3", Key::F3), ("f4", Key::F4), ("f5", Key::F5),
+    ("f6", Key::F6), ("f7", Key::F7), ("f8", Key::F8), ("f9", Key::F9), ("f10", Key::F10),
+    ("f11", Key::F11), ("f12", Key::F12),
+    ("kp0", Key::Kp0), ("kp1", Key::Kp1), ("kp2", Key::Kp2), ("kp3", Key::Kp3), ("kp4", Key::Kp4),
+    ("kp5", Key::Kp5), ("kp6", Key::Kp6), ("kp7", Key::Kp7), ("kp8", Key::Kp8), ("kp9", Key::Kp9),
+    ("kpdecimal", Key::KpDecimal), ("kpdivide", Key::KpDivide),
+    ("kpmultiply", Key::KpMultiply), ("kpsubtract", Key::KpSubtract), ("kpadd", Key::KpAdd),
+    ("kpenter", Key::KpEnter), ("kpequal", Key::KpEqual),
+    ("leftshift", Key::LeftShift), ("leftcontrol", Key::LeftControl), ("leftalt", Key::LeftAlt),
+    ("leftsuper", Key::LeftSuper), ("rightshift", Key::RightShift),
+    ("rightcontrol", Key::RightControl), ("rightalt", Key::RightAlt),
+    ("rightsuper", Key::RightSuper), ("menu", Key::Menu),
+];
+
+const MOUSE_BUTTONS: &[(&str, MouseButton)] = &[
+    ("left", MouseButton::Button1),
+    ("right", MouseButton::Button2),
+    ("middle", MouseButton::Button3),
+    ("1", MouseButton::Button1),
+    ("2", MouseButton::Button2),
+    ("3", MouseButton::Button3),
+    ("4", MouseButton::Button4),
+    ("5", MouseButton::Button5),
+    ("6", MouseButton::Button6),
+    ("7", MouseButton::Button7),
+    ("8", MouseButton::Button8),
+];
+
+const GAMEPAD_BUTTONS: &[(&str, GamepadButton)] = &[
+    ("a", GamepadButton::ButtonA),
+    ("b", GamepadButton::ButtonB),
+    ("x", GamepadButton::ButtonX),
+    ("y", GamepadButton::ButtonY),
+    ("leftbumper", GamepadButton::ButtonLeftBumper),
+    ("rightbumper", GamepadButton::ButtonRightBumper),
+    ("back", GamepadButton::ButtonBack),
+    ("start", GamepadButton::ButtonStart),
+    ("guide", GamepadButton::ButtonGuide),
+    ("leftthumb", GamepadButton::ButtonLeftThumb),
+    ("rightthumb", GamepadButton::ButtonRightThumb),
+    ("dpadup", GamepadButton::ButtonDpadUp),
+    ("dpadright", GamepadButton::ButtonDpadRight),
+    ("dpaddown", GamepadButton::ButtonDpadDown),
+    ("dpadleft", GamepadButton::ButtonDpadLeft),
+];
+
+const GAMEPAD_AXES: &[(&str, GamepadAxis)] = &[
+    ("leftx", GamepadAxis::AxisLeftX),
+    ("lefty", GamepadAxis::AxisLeftY),
+    ("rightx", GamepadAxis::AxisRightX),
+    ("righty", GamepadAxis::AxisRightY),
+    ("lefttrigger", GamepadAxis::AxisLeftTrigger),
+    ("righttrigger", GamepadAxis::AxisRightTrigger),
+];
@@ -0,0 +1,268 @@
+use crate::result::{error, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{LineWriter, Write as _};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The targets each part of the renderer logs under, which `[logging.targets]` can filter on
+pub(crate) mod target {
+    /// Loading Vulkan and creating the instance
+    pub const INSTANCE: &str = "instance";
+    /// Picking and creating the device, and the optional features it has
+    pub const DEVICE: &str = "device";
+    pub const SWAPCHAIN: &str = "swapchain";
+    /// Loading textures, meshes and shaders
+    pub const ASSETS: &str = "assets";
+    /// The main loop and frame timings
+    pub const FRAME: &str = "frame";
+    pub const WINDOW: &str = "window";
+    /// Messages from the validation layers
+    pub const VALIDATION: &str = "validation";
+    /// `debug_printf!` output from shaders
+    pub const SHADER: &str = "shader";
+}
+
+/// The `[logging]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct LoggingConfig {
+    /// Messages less severe than this aren't written
+    pub level: LevelFilter,
+    /// Levels for particular targets, in place of `level`
+    pub targets: BTreeMap<String, LevelFilter>,
+    /// Also write every message to this file as a line of JSON, to attach to bug reports
+    pub file: Option<PathBuf>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            targets: BTreeMap::new(),
+            file: None,
+        }
+    }
+}
+
+/// A `log::LevelFilter` which can be read from the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LevelFilter {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LevelFilter> for log::LevelFilter {
+    fn from(level: LevelFilter) -> Self {
+        match level {
+            LevelFilter::Off => Self::Off,
+            LevelFilter::Error => Self::Error,
+            LevelFilter::Warn => Self::Warn,
+            LevelFilter::Info => Self::Info,
+            LevelFilter::Debug => Self::Debug,
+            LevelFilter::Trace => Self::Trace,
+        }
+    }
+}
+
+struct Filters {
+    level: log::LevelFilter,
+    targets: Vec<(String, log::LevelFilter)>,
+}
+
+impl Filters {
+    /// The level for `target`, from the most specific entry in `targets` which matches it
+    fn level(&self, target: &str) -> log::LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.level, |&(_, level)| level)
+    }
+}
+
+/// Writes log records to stderr, and as JSON lines to the file in the config if there is one
+struct Logger {
+    filters: RwLock<Filters>,
+    file: Mutex<Option<LineWriter<File>>>,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let filters = self.filters.read().unwrap_or_else(|e| e.into_inner());
+        metadata.level() <= filters.level(metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        eprintln!(
+            "[{:<5} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = file.as_mut() {
+            // Nowhere to report a failure to write a log, so carry on without it
+            _ = writeln!(file, "{}", json_line(record));
+        }
+    }
+
+    fn flush(&self) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = file.as_mut() {
+            _ = file.flush();
+        }
+    }
+}
+
+static LOGGER: Logger = Logger {
+    filters: RwLock::new(Filters {
+        level: log::LevelFilter::Info,
+        targets: Vec::new(),
+    }),
+    file: Mutex::new(None),
+};
+
+/// Install the logger with the default settings, so messages from before the config is loaded
+///  aren't lost. Only the first call has any effect
+pub(crate) fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+}
+
+/// Apply the `[logging]` section of the config, opening the JSON log file if there is one
+pub(crate) fn configure(config: &LoggingConfig) -> Result<()> {
+    let filters = Filters {
+        level: config.level.into(),
+        targets: config
+            .targets
+            .iter()
+            .map(|(target, &level)| (target.clone(), level.into()))
+            .collect(),
+    };
+    let max_level = filters
+        .targets
+        .iter()
+        .map(|&(_, level)| level)
+        .fold(filters.level, Ord::max);
+    *LOGGER.filters.write().unwrap_or_else(|e| e.into_inner()) = filters;
+    log::set_max_level(max_level);
+
+    if let Some(path) = &config.file {
+        let file = match File::create(path) {
+            Ok(file) => file,
+            Err(e) => return error(&format!("Failed to create {}: {e}", path.display())),
+        };
+        *LOGGER.file.lock().unwrap_or_else(|e| e.into_inner()) = Some(LineWriter::new(file));
+    }
+
+    Ok(())
+}
+
+/// `record` as a JSON object, with the time in seconds since the Unix epoch
+fn json_line(record: &log::Record) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+
+    let mut line = format!(
+        "{{\"time\":{time:.3},\"level\":\"{}\",\"target\":",
+        record.level()
+    );
+    push_json_string(&mut line, record.target());
+    line.push_str(",\"message\":");
+    push_json_string(&mut line, &record.args().to_string());
+    line.push('}');
+    line
+}
+
+fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => _ = write!(out, "\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> Filters {
+        Filters {
+            level: log::LevelFilter::Warn,
+            targets: vec![
+                ("device".to_owned(), log::LevelFilter::Debug),
+                ("device::alloc::large".to_owned(), log::LevelFilter::Trace),
+                ("device::alloc".to_owned(), log::LevelFilter::Error),
+            ],
+        }
+    }
+
+    #[test]
+    fn level_matches_whole_path_segments() {
+        let filters = filters();
+        assert_eq!(filters.level("device"), log::LevelFilter::Debug);
+        assert_eq!(filters.level("device::queue"), log::LevelFilter::Debug);
+        assert_eq!(filters.level("devices"), log::LevelFilter::Warn);
+        assert_eq!(filters.level("device::allocator"), log::LevelFilter::Debug);
+        assert_eq!(filters.level("swapchain"), log::LevelFilter::Warn);
+    }
+
+    #[test]
+    fn level_picks_most_specific_entry() {
+        let filters = filters();
+        assert_eq!(filters.level("device::alloc"), log::LevelFilter::Error);
+        assert_eq!(
+            filters.level("device::alloc::small"),
+            log::LevelFilter::Error
+        );
+        assert_eq!(
+            filters.level("device::alloc::large"),
+            log::LevelFilter::Trace
+        );
+    }
+
+    fn json_string(value: &str) -> String {
+        let mut out = String::new();
+        push_json_string(&mut out, value);
+        out
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("plain"), r#""plain""#);
+        assert_eq!(json_string(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(json_string(r"C:\path"), r#""C:\\path""#);
+        assert_eq!(json_string("a\nb\rc\td"), r#""a\nb\rc\td""#);
+        assert_eq!(json_string("\u{1}\u{1f}\u{7f}"), r#""\u0001\u001f\u007f""#);
+        assert_eq!(json_string("ünïcode ✓"), "\"ünïcode ✓\"");
+    }
+}
@@ -1,7 +1,28 @@
 #![warn(clippy::all)]
 
+mod bindless;
+mod camera;
+mod config;
+mod debug_messenger;
+mod debug_utils;
+mod deletion_queue;
+mod descriptor_allocator;
+mod display;
+mod frame_stats;
+mod frame_sync;
+mod gpu_culling;
+mod gpu_queries;
+mod gpu_timer;
+mod image_barrier;
+mod input;
+mod logging;
+mod output_pass;
+mod owned;
+mod pod;
 mod result;
+mod scene;
 mod vulkan_app;
+mod window;
 
 use crate::vulkan_app::VulkanApp;
 use std::error::Error;
@@ -11,7 +32,10 @@ fn main() -> ExitCode {
     let res = main_impl();
 
     if let Err(e) = res {
-        eprintln!("{e}");
+        // Errors can happen before the logger is set up, e.g. loading the config
+        logging::init();
+        log::error!("{e}");
+        log::logger().flush();
         return ExitCode::FAILURE;
     }
 
@@ -21,7 +45,6 @@ fn main() -> ExitCode {
 fn main_impl() -> Result<(), Box<dyn Error>> {
     let mut app = VulkanApp::new()?;
     app.run();
-    app.cleanup();
 
     Ok(())
 }
@@ -0,0 +1,428 @@
+use crate::descriptor_allocator::DescriptorWriter;
+use crate::owned::{self, Owned};
+use crate::pod;
+use crate::result::Result;
+use crate::vulkan_app::VulkanApp;
+use shared::{
+    OutputPushConstants, OUTPUT_TRANSFORM_LINEAR, OUTPUT_TRANSFORM_PQ, OUTPUT_TRANSFORM_SCRGB,
+    OUTPUT_TRANSFORM_SRGB,
+};
+use std::slice;
+
+/// Format the scene is rendered in, before being converted for the swapchain
+pub(crate) const SCENE_FORMAT: ash::vk::Format = ash::vk::Format::R16G16B16A16_SFLOAT;
+
+/// The render pass and the pipeline using it, which are made for one swapchain format
+pub(crate) type FormatObjects = (
+    Owned<ash::vk::RenderPass>,
+    Owned<ash::vk::PipelineLayout>,
+    Owned<ash::vk::Pipeline>,
+);
+
+/// The final pass of each frame: reads the linear FP16 scene colour and writes it to the
+/// swapchain image, encoded for the swapchain's format and colour space.
+pub(crate) struct OutputPass {
+    /// Depends on the swapchain, so is recreated along with it. `None` until `create_targets`
+    targets: Option<OutputTargets>,
+
+    /// Depends on the swapchain format, so is recreated by `recreate_for_format`
+    pub render_pass: Owned<ash::vk::RenderPass>,
+    pub pipeline_layout: Owned<ash::vk::PipelineLayout>,
+    pub pipeline: Owned<ash::vk::Pipeline>,
+    pub sampler: Owned<ash::vk::Sampler>,
+    pub descriptor_set_layout: Owned<ash::vk::DescriptorSetLayout>,
+    /// Brightness in nits of a scene value of 1.0 on HDR outputs
+    pub paper_white_nits: f32,
+    /// Format `render_pass` was made for
+    swapchain_format: ash::vk::Format,
+}
+
+/// The scene image and the framebuffers, which are sized to the swapchain.
+///
+/// Each set of targets has its own descriptor set, so new targets can be made while frames using
+/// the old ones are still in flight.
+pub(crate) struct OutputTargets {
+    /// Freeing the pool frees `descriptor_set`
+    pub descriptor_pool: Owned<ash::vk::DescriptorPool>,
+    /// Reads `scene_image`
+    pub descriptor_set: ash::vk::DescriptorSet,
+    /// Framebuffers of the output render pass, one per swapchain image
+    pub framebuffers: Vec<Owned<ash::vk::Framebuffer>>,
+    /// Framebuffer of the scene render pass, targeting `scene_image`
+    pub scene_framebuffer: Owned<ash::vk::Framebuffer>,
+    pub scene_image_view: Owned<ash::vk::ImageView>,
+    /// The scene is rendered into this image
+    pub scene_image: Owned<ash::vk::Image>,
+    pub scene_image_memory: Owned<ash::vk::DeviceMemory>,
+}
+
+impl OutputPass {
+    /// Create the pipeline for the pass. `create_targets` must be called before it's used.
+    pub fn new(
+        device: &owned::Device,
+        shader_module: ash::vk::ShaderModule,
+        swapchain_format: ash::vk::Format,
+        paper_white_nits: f32,
+    ) -> Result<Self> {
+        let render_pass = Self::create_render_pass(device, swapchain_format)?;
+
+        let sampler_binding = ash::vk::DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_count(1)
+            .descriptor_type(ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .stage_flags(ash::vk::ShaderStageFlags::FRAGMENT);
+        let layout_info = ash::vk::DescriptorSetLayoutCreateInfo::default()
+            .bindings(slice::from_ref(&sampler_binding));
+        let descriptor_set_layout = unsafe {
+            Owned::new(
+                device,
+                device.create_descriptor_set_layout(&layout_info, None)?,
+            )
+        };
+
+        let (pipeline_layout, pipeline) =
+            Self::create_pipeline(device, shader_module, *render_pass, *descriptor_set_layout)?;
+
+        // The scene image is the same size as the output, so there's no filtering to do
+        let sampler_info = ash::vk::SamplerCreateInfo::default()
+            .mag_filter(ash::vk::Filter::NEAREST)
+            .min_filter(ash::vk::Filter::NEAREST)
+            .address_mode_u(ash::vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(ash::vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(ash::vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .max_lod(0.0);
+        let sampler = unsafe { Owned::new(device, device.create_sampler(&sampler_info, None)?) };
+
+        Ok(Self {
+            targets: None,
+            render_pass,
+            pipeline_layout,
+            pipeline,
+            sampler,
+            descriptor_set_layout,
+            paper_white_nits,
+            swapchain_format,
+        })
+    }
+    /// Rebuild the render pass and pipeline if the swapchain format has changed, returning the
+    /// old ones to be retired. A change of colour space alone needs nothing rebuilt, as it's only
+    /// passed to the shader as a push constant.
+    ///
+    /// Must be called before `create_targets`, as the framebuffers use the render pass.
+    pub fn recreate_for_format(
+        &mut self,
+        device: &owned::Device,
+        shader_module: ash::vk::ShaderModule,
+        swapchain_format: ash::vk::Format,
+    ) -> Result<Option<FormatObjects>> {
+        if swapchain_format == self.swapchain_format {
+            return Ok(None);
+        }
+
+        let render_pass = Self::create_render_pass(device, swapchain_format)?;
+        let (pipeline_layout, pipeline) = Self::create_pipeline(
+            device,
+            shader_module,
+            *render_pass,
+            *self.descriptor_set_layout,
+        )?;
+        self.swapchain_format = swapchain_format;
+
+        Ok(Some((
+            std::mem::replace(&mut self.render_pass, render_pass),
+            std::mem::replace(&mut self.pipeline_layout, pipeline_layout),
+            std::mem::replace(&mut self.pipeline, pipeline),
+        )))
+    }
+    fn create_render_pass(
+        device: &owned::Device,
+        swapchain_format: ash::vk::Format,
+    ) -> Result<Owned<ash::vk::RenderPass>> {
+        // Every pixel is overwritten, so the old contents don't matter
+        let colour_attachment = ash::vk::AttachmentDescription::default()
+            .format(swapchain_format)
+            .samples(ash::vk::SampleCountFlags::TYPE_1)
+            .load_op(ash::vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(ash::vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(ash::vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(ash::vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(ash::vk::ImageLayout::UNDEFINED)
+            .final_layout(ash::vk::ImageLayout::PRESENT_SRC_KHR);
+
+        let attachment_ref = ash::vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(ash::vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let subpass = ash::vk::SubpassDescription::default()
+            .pipeline_bind_point(ash::vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(slice::from_ref(&attachment_ref));
+
+        // Wait for the swapchain image to be acquired. Reading the scene image is synchronised by
+        //  the scene render pass' outgoing dependency
+        let dependency = ash::vk::SubpassDependency::default()
+            .src_subpass(ash::vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(ash::vk::AccessFlags::empty())
+            .dst_stage_mask(ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_access_mask(ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+
+        let render_pass_info = ash::vk::RenderPassCreateInfo::default()
+            .attachments(slice::from_ref(&colour_attachment))
+            .subpasses(slice::from_ref(&subpass))
+            .dependencies(slice::from_ref(&dependency));
+
+        let render_pass = unsafe { device.create_render_pass(&render_pass_info, None) }?;
+        let render_pass = unsafe { Owned::new(device, render_pass) };
+        Ok(render_pass)
+    }
+    fn create_pipeline(
+        device: &owned::Device,
+        shader_module: ash::vk::ShaderModule,
+        render_pass: ash::vk::RenderPass,
+        descriptor_set_layout: ash::vk::DescriptorSetLayout,
+    ) -> Result<(Owned<ash::vk::PipelineLayout>, Owned<ash::vk::Pipeline>)> {
+        let shader_stages = [
+            ash::vk::PipelineShaderStageCreateInfo::default()
+                .stage(ash::vk::ShaderStageFlags::VERTEX)
+                .module(shader_module)
+                .name(c"output_vs"),
+            ash::vk::PipelineShaderStageCreateInfo::default()
+                .stage(ash::vk::ShaderStageFlags::FRAGMENT)
+                .module(shader_module)
+                .name(c"output_fs"),
+        ];
+
+        // The vertex shader makes its own full screen triangle, so there's no vertex input
+        let vertex_input_info = ash::vk::PipelineVertexInputStateCreateInfo::default();
+        let input_assembly = ash::vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(ash::vk::PrimitiveTopology::TRIANGLE_LIST)
+            .primitive_restart_enable(false);
+
+        let dynamic_states = [
+            ash::vk::DynamicState::VIEWPORT,
+            ash::vk::DynamicState::SCISSOR,
+        ];
+        let dynamic_state =
+            ash::vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+        let viewport_state = ash::vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterizer = ash::vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(ash::vk::PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(ash::vk::CullModeFlags::NONE);
+        let multisampling = ash::vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(ash::vk::SampleCountFlags::TYPE_1);
+
+        let colour_blend_attachment = ash::vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(ash::vk::ColorComponentFlags::RGBA)
+            .blend_enable(false);
+        let colour_blending = ash::vk::PipelineColorBlendStateCreateInfo::default()
+            .attachments(slice::from_ref(&colour_blend_attachment));
+
+        let push_constant_range = ash::vk::PushConstantRange::default()
+            .stage_flags(ash::vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(size_of::<OutputPushConstants>() as u32);
+        let pipeline_layout_info = ash::vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(slice::from_ref(&descriptor_set_layout))
+            .push_constant_ranges(slice::from_ref(&push_constant_range));
+        let pipeline_layout = unsafe {
+            Owned::new(
+                device,
+                device.create_pipeline_layout(&pipeline_layout_info, None)?,
+            )
+        };
+
+        let pipeline_info = ash::vk::GraphicsPipelineCreateInfo::default()
+            .stages(&shader_stages)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterizer)
+            .multisample_state(&multisampling)
+            .color_blend_state(&colour_blending)
+            .dynamic_state(&dynamic_state)
+            .layout(*pipeline_layout)
+            .render_pass(render_pass)
+            .subpass(0);
+
+        let pipeline = unsafe {
+            device.create_graphics_pipelines(
+                ash::vk::PipelineCache::null(),
+                slice::from_ref(&pipeline_info),
+                None,
+            )
+        }
+        .map_err(|(_, e)| e)?;
+
+        Ok((pipeline_layout, unsafe { Owned::new(device, pipeline[0]) }))
+    }
+
+    /// Create the scene image and the framebuffers for the current swapchain
+    pub fn create_targets(
+        &mut self,
+        instance: &ash::Instance,
+        device: &owned::Device,
+        physical_device: ash::vk::PhysicalDevice,
+        scene_render_pass: ash::vk::RenderPass,
+        swapchain_image_views: &[Owned<ash::vk::ImageView>],
+        extent: ash::vk::Extent2D,
+    ) -> Result<()> {
+        let (scene_image, scene_image_memory) = VulkanApp::create_image(
+            instance,
+            device,
+            physical_device,
+            extent.width,
+            extent.height,
+            SCENE_FORMAT,
+            ash::vk::ImageTiling::OPTIMAL,
+            ash::vk::ImageUsageFlags::COLOR_ATTACHMENT | ash::vk::ImageUsageFlags::SAMPLED,
+            ash::vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        let scene_image_view = VulkanApp::create_image_view(device, *scene_image, SCENE_FORMAT)?;
+
+        let scene_framebuffer = VulkanApp::create_framebuffers(
+            device,
+            slice::from_ref(&scene_image_view),
+            scene_render_pass,
+            extent,
+        )?
+        .remove(0);
+        let framebuffers = VulkanApp::create_framebuffers(
+            device,
+            swapchain_image_views,
+            *self.render_pass,
+            extent,
+        )?;
+
+        let pool_size = ash::vk::DescriptorPoolSize::default()
+            .ty(ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1);
+        let pool_info = ash::vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(slice::from_ref(&pool_size))
+            .max_sets(1);
+        let descriptor_pool =
+            unsafe { Owned::new(device, device.create_descriptor_pool(&pool_info, None)?) };
+
+        let alloc_info = ash::vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(*descriptor_pool)
+            .set_layouts(slice::from_ref(&*self.descriptor_set_layout));
+        let descriptor_set = unsafe { device.allocate_descriptor_sets(&alloc_info) }?[0];
+
+        DescriptorWriter::default()
+            .image(
+                0,
+                ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                *scene_image_view,
+                *self.sampler,
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .update(device, descriptor_set);
+
+        self.targets = Some(OutputTargets {
+            descriptor_pool,
+            descriptor_set,
+            framebuffers,
+            scene_framebuffer,
+            scene_image_view,
+            scene_image,
+            scene_image_memory,
+        });
+        Ok(())
+    }
+
+    /// # Panics
+    /// If `create_targets` hasn't been called since the last `take_targets`
+    pub fn targets(&self) -> &OutputTargets {
+        self.targets
+            .as_ref()
+            .expect("create_targets must be called before the pass is used")
+    }
+
+    /// Record the output render pass, converting the scene image into swapchain image
+    /// `image_index`. The scene render pass must already have been recorded.
+    pub fn cmd_draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: ash::vk::CommandBuffer,
+        image_index: u32,
+        extent: ash::vk::Extent2D,
+        surface_format: ash::vk::SurfaceFormatKHR,
+    ) {
+        let render_area = ash::vk::Rect2D::default().extent(extent);
+        let render_pass_info = ash::vk::RenderPassBeginInfo::default()
+            .render_pass(*self.render_pass)
+            .framebuffer(*self.targets().framebuffers[image_index as usize])
+            .render_area(render_area);
+
+        let push_constants = OutputPushConstants {
+            transform: output_transform(surface_format),
+            paper_white_nits: self.paper_white_nits,
+        };
+
+        let viewport = ash::vk::Viewport::default()
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_info,
+                ash::vk::SubpassContents::INLINE,
+            );
+            device.cmd_bind_pipeline(
+                command_buffer,
+                ash::vk::PipelineBindPoint::GRAPHICS,
+                *self.pipeline,
+            );
+            device.cmd_set_viewport(command_buffer, 0, slice::from_ref(&viewport));
+            device.cmd_set_scissor(command_buffer, 0, slice::from_ref(&render_area));
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                ash::vk::PipelineBindPoint::GRAPHICS,
+                *self.pipeline_layout,
+                0,
+                slice::from_ref(&self.targets().descriptor_set),
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                *self.pipeline_layout,
+                ash::vk::ShaderStageFlags::FRAGMENT,
+                0,
+                pod::bytes_of(&push_constants),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    /// Give up the targets, e.g. to retire them when the swapchain is recreated
+    pub fn take_targets(&mut self) -> Option<OutputTargets> {
+        self.targets.take()
+    }
+}
+
+/// The `OUTPUT_TRANSFORM_*` needed to show linear colour on a swapchain with `surface_format`
+fn output_transform(surface_format: ash::vk::SurfaceFormatKHR) -> u32 {
+    match surface_format.color_space {
+        ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT => OUTPUT_TRANSFORM_PQ,
+        ash::vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => OUTPUT_TRANSFORM_SCRGB,
+        _ if is_srgb_format(surface_format.format) => OUTPUT_TRANSFORM_LINEAR,
+        _ => OUTPUT_TRANSFORM_SRGB,
+    }
+}
+
+/// Whether the hardware applies the sRGB transfer function when writing to `format`
+fn is_srgb_format(format: ash::vk::Format) -> bool {
+    matches!(
+        format,
+        ash::vk::Format::B8G8R8A8_SRGB
+            | ash::vk::Format::R8G8B8A8_SRGB
+            | ash::vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
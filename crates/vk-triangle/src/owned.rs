@@ -0,0 +1,220 @@
+use crate::debug_messenger::DebugMessenger;
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// A shared reference to the instance, which is destroyed once the last one is dropped
+pub(crate) type Instance = Rc<InstanceOwner>;
+/// A shared reference to the device, which is destroyed once the last one is dropped
+pub(crate) type Device = Rc<DeviceOwner>;
+
+/// Owns the instance, along with the debug messenger reporting on it.
+///
+/// Everything made from the instance keeps an `Instance` to it, so it's only destroyed after
+///  all of them.
+pub(crate) struct InstanceOwner {
+    entry: ash::Entry,
+    instance: ash::Instance,
+    /// Dropped after the instance is destroyed, as the instance also reports through it
+    debug_messenger: Option<DebugMessenger>,
+}
+
+impl InstanceOwner {
+    /// Take ownership of `instance`, which was created with `debug_messenger`'s create info
+    pub fn new(
+        entry: ash::Entry,
+        instance: ash::Instance,
+        debug_messenger: Option<DebugMessenger>,
+    ) -> Instance {
+        Rc::new(Self {
+            entry,
+            instance,
+            debug_messenger,
+        })
+    }
+
+    pub fn entry(&self) -> &ash::Entry {
+        &self.entry
+    }
+
+    /// `None` if validation is disabled
+    pub fn debug_messenger(&self) -> Option<&DebugMessenger> {
+        self.debug_messenger.as_ref()
+    }
+}
+
+impl Deref for InstanceOwner {
+    type Target = ash::Instance;
+
+    fn deref(&self) -> &Self::Target {
+        &self.instance
+    }
+}
+
+impl Drop for InstanceOwner {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(debug_messenger) = &mut self.debug_messenger {
+                debug_messenger.destroy_messenger();
+            }
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+/// A surface, destroyed before the instance it was made from
+pub(crate) struct Surface {
+    surface: ash::vk::SurfaceKHR,
+    surface_instance: ash::khr::surface::Instance,
+    _instance: Instance,
+}
+
+impl Surface {
+    pub fn new(instance: &Instance, surface: ash::vk::SurfaceKHR) -> Self {
+        Self {
+            surface,
+            surface_instance: ash::khr::surface::Instance::new(instance.entry(), instance),
+            _instance: instance.clone(),
+        }
+    }
+
+    /// The extension functions for querying and destroying surfaces
+    pub fn surface_instance(&self) -> &ash::khr::surface::Instance {
+        &self.surface_instance
+    }
+}
+
+impl Deref for Surface {
+    type Target = ash::vk::SurfaceKHR;
+
+    fn deref(&self) -> &Self::Target {
+        &self.surface
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        unsafe { self.surface_instance.destroy_surface(self.surface, None) };
+    }
+}
+
+/// Owns the logical device, and keeps the instance alive until it's destroyed.
+///
+/// Every `Owned` object keeps a `Device` to it, so it's only destroyed after all of them.
+pub(crate) struct DeviceOwner {
+    device: ash::Device,
+    /// To destroy swapchains with
+    swapchain_device: ash::khr::swapchain::Device,
+    _instance: Instance,
+}
+
+impl DeviceOwner {
+    pub fn new(instance: &Instance, device: ash::Device) -> Device {
+        Rc::new(Self {
+            swapchain_device: ash::khr::swapchain::Device::new(instance, &device),
+            device,
+            _instance: instance.clone(),
+        })
+    }
+}
+
+impl Deref for DeviceOwner {
+    type Target = ash::Device;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+impl Drop for DeviceOwner {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_device(None) };
+    }
+}
+
+/// A handle to an object made from the device, which can be destroyed by an `Owned` wrapper
+pub(crate) trait DeviceObject: Copy {
+    /// # Safety
+    /// `self` must have been made from `device`, and no longer be in use by the GPU
+    unsafe fn destroy(self, device: &DeviceOwner);
+}
+
+macro_rules! device_objects {
+    ($($handle:ident => $destroy:ident,)*) => {
+        $(
+            impl DeviceObject for ash::vk::$handle {
+                unsafe fn destroy(self, device: &DeviceOwner) {
+                    unsafe { device.$destroy(self, None) };
+                }
+            }
+        )*
+    };
+}
+
+device_objects! {
+    Buffer => destroy_buffer,
+    DeviceMemory => free_memory,
+    Image => destroy_image,
+    ImageView => destroy_image_view,
+    Sampler => destroy_sampler,
+    ShaderModule => destroy_shader_module,
+    RenderPass => destroy_render_pass,
+    Framebuffer => destroy_framebuffer,
+    DescriptorSetLayout => destroy_descriptor_set_layout,
+    DescriptorPool => destroy_descriptor_pool,
+    PipelineLayout => destroy_pipeline_layout,
+    Pipeline => destroy_pipeline,
+    CommandPool => destroy_command_pool,
+    QueryPool => destroy_query_pool,
+    Semaphore => destroy_semaphore,
+    Fence => destroy_fence,
+}
+
+impl DeviceObject for ash::vk::SwapchainKHR {
+    unsafe fn destroy(self, device: &DeviceOwner) {
+        unsafe { device.swapchain_device.destroy_swapchain(self, None) };
+    }
+}
+
+/// Owns `handle`, destroying it when dropped.
+///
+/// Dropping it doesn't wait for the GPU, so the owner must make sure the object isn't in use
+///  any more, e.g. by waiting for the device to be idle first.
+pub(crate) struct Owned<T: DeviceObject> {
+    handle: T,
+    device: Device,
+}
+
+impl<T: DeviceObject> Owned<T> {
+    /// Take ownership of `handle`, destroying it when this is dropped
+    ///
+    /// # Safety
+    /// `handle` must have been made from `device`, and nothing else may destroy it. The owner
+    ///  must make sure the GPU has finished with it before this is dropped
+    pub unsafe fn new(device: &Device, handle: T) -> Self {
+        Self {
+            handle,
+            device: device.clone(),
+        }
+    }
+}
+
+impl<T: DeviceObject> Deref for Owned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+impl<T: DeviceObject> Drop for Owned<T> {
+    fn drop(&mut self) {
+        // Safety: `new` requires `handle` to be from `device`, and the owner is responsible for
+        //  the GPU having finished with it
+        unsafe { self.handle.destroy(&self.device) };
+    }
+}
+
+/// The handles of each of `owned`, e.g. to write into descriptors or name them
+pub(crate) fn handles<T: DeviceObject>(owned: &[Owned<T>]) -> Vec<T> {
+    owned.iter().map(|owned| **owned).collect()
+}
@@ -0,0 +1,32 @@
+use shared::{CullPushConstants, OutputPushConstants, PushConstants};
+use std::slice;
+
+/// Plain data that can be read as bytes, such as push constants.
+///
+/// # Safety
+/// Implementors must be `#[repr(C)]` with no padding bytes, so that every byte of a value is
+/// initialised. Pair each impl with `assert_no_padding!` so a layout change fails to compile.
+pub(crate) unsafe trait Pod: Sized {}
+
+/// View `value` as its bytes
+pub(crate) fn bytes_of<T: Pod>(value: &T) -> &[u8] {
+    // Safety: `value` is a valid reference, so is readable for `size_of::<T>()` bytes, and `Pod`
+    //  guarantees none of those bytes are padding
+    unsafe { slice::from_raw_parts((value as *const T).cast::<u8>(), size_of::<T>()) }
+}
+
+/// Fail to compile if the size of `$ty` isn't exactly the sum of its fields' sizes
+macro_rules! assert_no_padding {
+    ($ty:ty, $($field:ty),+ $(,)?) => {
+        const _: () = assert!(size_of::<$ty>() == 0 $(+ size_of::<$field>())+);
+    };
+}
+
+assert_no_padding!(PushConstants, u32);
+unsafe impl Pod for PushConstants {}
+
+assert_no_padding!(CullPushConstants, [glam::Vec4; 6], u32, [u32; 3]);
+unsafe impl Pod for CullPushConstants {}
+
+assert_no_padding!(OutputPushConstants, u32, f32);
+unsafe impl Pod for OutputPushConstants {}
@@ -0,0 +1,142 @@
+use glam::{Mat4, Quat, Vec3, Vec4};
+use shared::InstanceData;
+
+/// Index into the meshes uploaded by `VulkanApp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MeshHandle(pub usize);
+
+/// Index into the materials loaded by `VulkanApp`, resolved to texture indices in `ObjectData`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MaterialHandle(pub u32);
+
+/// A range of the shared vertex and index buffers that makes up one mesh
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Mesh {
+    pub index_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    /// Radius of a sphere around the origin containing every vertex
+    pub bounding_radius: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation_scale(translation: Vec3, scale: f32) -> Self {
+        Self {
+            translation,
+            scale: Vec3::splat(scale),
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// The instance used for objects that aren't instanced: no extra transform, and no tint
+pub(crate) const DEFAULT_INSTANCE: InstanceData = InstanceData {
+    model_x: Vec4::X,
+    model_y: Vec4::Y,
+    model_z: Vec4::Z,
+    model_w: Vec4::W,
+    colour: Vec4::ONE,
+};
+
+pub(crate) fn instance(transform: Mat4, colour: Vec4) -> InstanceData {
+    InstanceData {
+        model_x: transform.x_axis,
+        model_y: transform.y_axis,
+        model_z: transform.z_axis,
+        model_w: transform.w_axis,
+        colour,
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SceneObject {
+    pub mesh: MeshHandle,
+    pub material: MaterialHandle,
+    pub transform: Transform,
+    /// Copies of the mesh to draw in a single call, each relative to `transform`.
+    ///
+    /// If empty, the object is drawn once with `DEFAULT_INSTANCE`.
+    pub instances: Vec<InstanceData>,
+}
+
+impl SceneObject {
+    /// World space bounding sphere of the object and all its instances, with the radius in `w`
+    pub fn bounding_sphere(&self, mesh: &Mesh) -> Vec4 {
+        let local_radius = if self.instances.is_empty() {
+            mesh.bounding_radius
+        } else {
+            self.instances
+                .iter()
+                .map(|instance| {
+                    let scale = [instance.model_x, instance.model_y, instance.model_z]
+                        .map(|axis| axis.truncate().length())
+                        .into_iter()
+                        .fold(0.0, f32::max);
+                    instance.model_w.truncate().length() + mesh.bounding_radius * scale
+                })
+                .fold(0.0, f32::max)
+        };
+
+        let centre = self.transform.translation;
+        let radius = local_radius * self.transform.scale.abs().max_element();
+        centre.extend(radius)
+    }
+}
+
+/// Everything that gets drawn each frame, in draw order
+#[derive(Default)]
+pub(crate) struct Scene {
+    objects: Vec<SceneObject>,
+}
+
+impl Scene {
+    pub fn add_object(&mut self, mesh: MeshHandle, material: MaterialHandle, transform: Transform) {
+        self.add_instanced_object(mesh, material, transform, Vec::new());
+    }
+
+    pub fn add_instanced_object(
+        &mut self,
+        mesh: MeshHandle,
+        material: MaterialHandle,
+        transform: Transform,
+        instances: Vec<InstanceData>,
+    ) {
+        self.objects.push(SceneObject {
+            mesh,
+            material,
+            transform,
+            instances,
+        });
+    }
+
+    pub fn objects(&self) -> &[SceneObject] {
+        &self.objects
+    }
+
+    pub fn objects_mut(&mut self) -> &mut [SceneObject] {
+        &mut self.objects
+    }
+}
@@ -1,87 +1,185 @@
+use crate::bindless::{BindlessTexture, BindlessTextures};
+use crate::camera::Camera;
+use crate::config::Config;
+use crate::debug_messenger::{DebugMessenger, ValidationCounts};
+use crate::debug_utils::DebugUtils;
+use crate::deletion_queue::DeletionQueue;
+use crate::descriptor_allocator::{DescriptorAllocator, DescriptorWriter, PoolRatio};
+use crate::display::{FrameLimiter, PresentMode};
+use crate::frame_stats::{FramePhase, FrameStats};
+use crate::frame_sync::{FrameSync, Submit};
+use crate::gpu_culling::{self, GpuCulling};
+use crate::gpu_queries::{GpuQueries, PipelineStatistics};
+use crate::gpu_timer::GpuTimer;
+use crate::image_barrier::{
+    cmd_transition_image_layout, cmd_transition_image_layout2, ImageTransition,
+};
+use crate::input::{bindings, Input};
+use crate::logging::{self, target};
+use crate::output_pass::{OutputPass, SCENE_FORMAT};
+use crate::owned::{self, DeviceOwner, InstanceOwner, Owned};
+use crate::pod::{self, Pod};
 use crate::result::{err, error, Result};
-use glfw::{ClientApiHint, Glfw, PWindow, WindowHint, WindowMode};
-use shared::{UniformBufferObject, VertexData};
+use crate::scene::{self, MaterialHandle, Mesh, MeshHandle, Scene, Transform, DEFAULT_INSTANCE};
+use crate::window::{WindowConfig, WindowEvents, WindowManager};
+use glfw::{CursorMode, Glfw, PWindow};
+use shared::{
+    CullObject, CullPushConstants, DrawIndexedIndirectCommand, InstanceData, ObjectData,
+    PushConstants, UniformBufferObject, VertexData, INDIRECT_OBJECT_INDEX,
+};
 use std::collections::BTreeSet;
-use std::f32::consts::PI;
+use std::f32::consts::{PI, SQRT_2};
 use std::fmt::Debug;
 use std::mem::{offset_of, MaybeUninit};
+use std::ops::Range;
 use std::path::Path;
 use std::ptr::null;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, LazyLock};
-use std::time::{Duration, Instant};
+use std::time::Instant;
 use std::{ffi, ptr, slice};
-use std::thread::sleep;
 
 const VALIDATION_LAYERS: &[*const ffi::c_char] = &[c"VK_LAYER_KHRONOS_validation".as_ptr()];
 const ENABLE_VALIDATION: bool = cfg!(any(debug_assertions, not(debug_assertions)));
 static DEVICE_EXTENSIONS: &[&ffi::CStr] = &[ash::vk::KHR_SWAPCHAIN_NAME];
-const MAX_FRAMES_IN_FLIGHT: u32 = 2;
+/// Capacity of the per-frame `ObjectData` storage buffers
+const MAX_OBJECTS: u32 = 1024;
+/// Capacity of the per-frame `InstanceData` vertex buffers
+const MAX_INSTANCES: u32 = 16384;
+/// Instanced objects are drawn as a `INSTANCE_GRID_SIZE` x `INSTANCE_GRID_SIZE` grid
+const INSTANCE_GRID_SIZE: u32 = 48;
+/// Descriptors per set in the pools for per-frame descriptor sets, matching the scene set layout
+const FRAME_POOL_RATIOS: [PoolRatio; 2] = [
+    PoolRatio {
+        ty: ash::vk::DescriptorType::UNIFORM_BUFFER,
+        per_set: 1.0,
+    },
+    PoolRatio {
+        ty: ash::vk::DescriptorType::STORAGE_BUFFER,
+        per_set: 2.0,
+    },
+];
+/// Sets in the first pool of each frame's descriptor allocator
+const FRAME_POOL_INITIAL_SETS: u32 = 8;
+const PUSH_CONSTANT_STAGES: ash::vk::ShaderStageFlags = ash::vk::ShaderStageFlags::from_raw(
+    ash::vk::ShaderStageFlags::VERTEX.as_raw() | ash::vk::ShaderStageFlags::FRAGMENT.as_raw(),
+);
 
-type DebugCallback = fn(
-    ash::vk::DebugUtilsMessageSeverityFlagsEXT,
-    ash::vk::DebugUtilsMessageTypeFlagsEXT,
-    ash::vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+static START_TIME: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Host visible buffers, one per frame in flight, with their memory and where it's mapped
+type MappedBuffers = (
+    Vec<Owned<ash::vk::Buffer>>,
+    Vec<Owned<ash::vk::DeviceMemory>>,
+    Vec<*mut ffi::c_void>,
 );
 
 pub(crate) struct VulkanApp {
+    /// Dropped first, as the surface must be destroyed before the window
+    vulkan: VulkanData,
+
     glfw: Glfw,
     window: PWindow,
-
-    vulkan: VulkanData,
+    events: WindowEvents,
+    window_manager: WindowManager,
+    input: Input,
+    swapchain_settings: SwapchainSettings,
+    frame_limiter: FrameLimiter,
+    frame_stats: FrameStats,
+
+    scene: Scene,
+    camera: Camera,
+    /// The instances used by each scene object, within the current frame's instance buffer
+    instance_ranges: Vec<Range<u32>>,
+    /// Cull and draw the scene on the GPU with indirect draws, rather than a draw call per object
+    gpu_driven: bool,
 
     current_frame: u32,
 
     framebuffer_resized: Arc<AtomicBool>,
 }
 
+/// Everything made through Vulkan.
+///
+/// Each object is destroyed when dropped, and keeps the device or instance it was made from
+///  alive until then. Dropping this waits for the device to be idle first.
 struct VulkanData {
-    pub entry: ash::Entry,
-    pub instance: ash::Instance,
-
-    pub debug_utils_instance: Option<ash::ext::debug_utils::Instance>,
-    pub debug_callback: Option<ash::vk::DebugUtilsMessengerEXT>,
-
-    pub surface_instance: ash::khr::surface::Instance,
-    pub surface: ash::vk::SurfaceKHR,
+    pub instance: owned::Instance,
+    pub debug_utils: DebugUtils,
 
     pub physical_device: ash::vk::PhysicalDevice,
-    pub device: ash::Device,
+    pub device_limits: ash::vk::PhysicalDeviceLimits,
+    pub device: owned::Device,
     pub graphics_queue: ash::vk::Queue,
     pub present_queue: ash::vk::Queue,
 
     pub swapchain_device: ash::khr::swapchain::Device,
-    pub swapchain: ash::vk::SwapchainKHR,
     pub swapchain_images: Vec<ash::vk::Image>,
-    pub swapchain_format: ash::vk::Format,
+    pub swapchain_surface_format: ash::vk::SurfaceFormatKHR,
     pub swapchain_extent: ash::vk::Extent2D,
-    pub swapchain_image_views: Vec<ash::vk::ImageView>,
-    pub swap_chain_framebuffers: Vec<ash::vk::Framebuffer>,
-
-    pub shader_module: ash::vk::ShaderModule,
-    pub render_pass: ash::vk::RenderPass,
-    pub descriptor_set_layout: ash::vk::DescriptorSetLayout,
-    pub pipeline_layout: ash::vk::PipelineLayout,
-    pub graphics_pipeline: ash::vk::Pipeline,
-    pub command_pool: ash::vk::CommandPool,
-    pub texture_image: ash::vk::Image,
-    pub texture_image_memory: ash::vk::DeviceMemory,
-    pub texture_image_view: ash::vk::ImageView,
-    pub texture_sampler: ash::vk::Sampler,
-    pub vertex_buffer: ash::vk::Buffer,
-    pub vertex_buffer_memory: ash::vk::DeviceMemory,
-    pub index_buffer: ash::vk::Buffer,
-    pub index_buffer_memory: ash::vk::DeviceMemory,
-    pub uniform_buffers: Vec<ash::vk::Buffer>,
-    pub uniform_buffers_memory: Vec<ash::vk::DeviceMemory>,
+    pub swapchain_present_mode: ash::vk::PresentModeKHR,
+    /// Declared before `swapchain`, so views of its images are dropped first
+    pub swapchain_image_views: Vec<Owned<ash::vk::ImageView>>,
+    /// Signalled when rendering to each swapchain image finishes, and waited on to present it.
+    ///  Per image rather than per frame, as it's only free again once the image is reacquired
+    pub render_finished_semaphores: Vec<Owned<ash::vk::Semaphore>>,
+    /// The number of the frame that last rendered to each swapchain image. Images can be acquired
+    ///  out of order, so this may be a different frame slot than the next one
+    pub images_in_flight: Vec<Option<u64>>,
+    pub swapchain: Owned<ash::vk::SwapchainKHR>,
+    pub hdr_metadata_device: Option<ash::ext::hdr_metadata::Device>,
+    /// Set on every HDR swapchain, if `VK_EXT_hdr_metadata` is enabled
+    pub hdr_metadata: Option<ash::vk::HdrMetadataEXT<'static>>,
+    /// Converts the scene colour for the swapchain, and owns the images the scene renders into
+    pub output_pass: OutputPass,
+    /// `None` if the graphics queue doesn't support timestamps
+    pub gpu_timer: Option<GpuTimer>,
+    pub gpu_queries: GpuQueries,
+
+    pub shader_module: Owned<ash::vk::ShaderModule>,
+    pub render_pass: Owned<ash::vk::RenderPass>,
+    pub descriptor_set_layout: Owned<ash::vk::DescriptorSetLayout>,
+    pub pipeline_layout: Owned<ash::vk::PipelineLayout>,
+    pub graphics_pipeline: Owned<ash::vk::Pipeline>,
+    pub command_pool: Owned<ash::vk::CommandPool>,
+    pub texture_image: Owned<ash::vk::Image>,
+    pub texture_image_memory: Owned<ash::vk::DeviceMemory>,
+    pub texture_image_view: Owned<ash::vk::ImageView>,
+    pub texture_sampler: Owned<ash::vk::Sampler>,
+    /// Every texture, bound at set 1 for the whole frame
+    pub bindless: BindlessTextures,
+    /// The texture of each material, indexed by `MaterialHandle`
+    pub materials: Vec<BindlessTexture>,
+    pub vertex_buffer: Owned<ash::vk::Buffer>,
+    pub vertex_buffer_memory: Owned<ash::vk::DeviceMemory>,
+    pub index_buffer: Owned<ash::vk::Buffer>,
+    pub index_buffer_memory: Owned<ash::vk::DeviceMemory>,
+    pub uniform_buffers: Vec<Owned<ash::vk::Buffer>>,
+    pub uniform_buffers_memory: Vec<Owned<ash::vk::DeviceMemory>>,
     pub uniform_buffers_mapped: Vec<*mut ffi::c_void>,
-    pub descriptor_pool: ash::vk::DescriptorPool,
+    pub object_buffers: Vec<Owned<ash::vk::Buffer>>,
+    pub object_buffers_memory: Vec<Owned<ash::vk::DeviceMemory>>,
+    pub object_buffers_mapped: Vec<*mut ffi::c_void>,
+    pub instance_buffers: Vec<Owned<ash::vk::Buffer>>,
+    pub instance_buffers_memory: Vec<Owned<ash::vk::DeviceMemory>>,
+    pub instance_buffers_mapped: Vec<*mut ffi::c_void>,
+    pub meshes: Vec<Mesh>,
+    pub gpu_culling: GpuCulling,
+    /// For sets only used by one frame, reset once the frame slot's last submission has finished
+    pub frame_descriptors: Vec<DescriptorAllocator>,
+    /// The scene descriptor set of each frame slot's latest frame, from its `frame_descriptors`
     pub descriptor_sets: Vec<ash::vk::DescriptorSet>,
+    /// The command buffers are freed along with the pool
     pub command_buffers: Vec<ash::vk::CommandBuffer>,
 
-    pub image_available_semaphores: Vec<ash::vk::Semaphore>,
-    pub render_finished_semaphores: Vec<ash::vk::Semaphore>,
-    pub in_flight_fences: Vec<ash::vk::Fence>,
+    pub image_available_semaphores: Vec<Owned<ash::vk::Semaphore>>,
+    pub frame_sync: FrameSync,
+    /// Objects replaced while frames using them may still be in flight. Declared before
+    ///  `surface`, as it may hold old swapchains
+    pub deletion_queue: DeletionQueue,
+
+    /// Declared after `swapchain`, which must be destroyed first
+    pub surface: owned::Surface,
 }
 
 #[rustfmt::skip] // This doesn't need to get shoved onto so many lines
@@ -112,7 +210,7 @@ const INDICES: [u16; 6] = [0, 1, 2, 2, 3, 0];
 
 trait VertexInputData {
     fn get_binding_description() -> ash::vk::VertexInputBindingDescription;
-    fn get_attribute_descriptions() -> [ash::vk::VertexInputAttributeDescription; 3];
+    fn get_attribute_descriptions() -> Vec<ash::vk::VertexInputAttributeDescription>;
 }
 
 impl VertexInputData for VertexData {
@@ -122,8 +220,8 @@ impl VertexInputData for VertexData {
             .stride(size_of::<VertexData>() as u32)
             .input_rate(ash::vk::VertexInputRate::VERTEX)
     }
-    fn get_attribute_descriptions() -> [ash::vk::VertexInputAttributeDescription; 3] {
-        [
+    fn get_attribute_descriptions() -> Vec<ash::vk::VertexInputAttributeDescription> {
+        vec![
             ash::vk::VertexInputAttributeDescription::default()
                 .binding(0)
                 .location(0)
@@ -143,17 +241,64 @@ impl VertexInputData for VertexData {
     }
 }
 
+impl VertexInputData for InstanceData {
+    fn get_binding_description() -> ash::vk::VertexInputBindingDescription {
+        ash::vk::VertexInputBindingDescription::default()
+            .binding(1)
+            .stride(size_of::<InstanceData>() as u32)
+            .input_rate(ash::vk::VertexInputRate::INSTANCE)
+    }
+    fn get_attribute_descriptions() -> Vec<ash::vk::VertexInputAttributeDescription> {
+        // Locations carry on from those used by `VertexData`
+        [
+            offset_of!(InstanceData, model_x),
+            offset_of!(InstanceData, model_y),
+            offset_of!(InstanceData, model_z),
+            offset_of!(InstanceData, model_w),
+            offset_of!(InstanceData, colour),
+        ]
+        .into_iter()
+        .zip(3..)
+        .map(|(offset, location)| {
+            ash::vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(location)
+                .format(ash::vk::Format::R32G32B32A32_SFLOAT)
+                .offset(offset as u32)
+        })
+        .collect()
+    }
+}
+
 impl VulkanApp {
     pub(crate) fn new() -> Result<Self> {
         // Initialise
-        println!("Creating vulkan app");
-        let (glfw, window, framebuffer_resized) = Self::init_window()?;
-        let vulkan = Self::init_vulkan(&glfw, &window)?;
+        logging::init();
+        let config = Config::load(Path::new(Config::PATH))?;
+        logging::configure(&config.logging)?;
+        let (glfw, window, events, window_manager, framebuffer_resized) =
+            Self::init_window(&config.window)?;
+        let swapchain_settings = SwapchainSettings {
+            present_mode: config.display.present_mode,
+            hdr: config.display.hdr.enabled,
+        };
+        let vulkan = Self::init_vulkan(&glfw, &window, swapchain_settings, &config)?;
+        let scene = Self::create_scene();
 
         Ok(Self {
             glfw,
             window,
+            events,
+            window_manager,
+            input: Input::new(config.input),
+            swapchain_settings,
+            frame_limiter: FrameLimiter::new(config.display.max_fps),
+            frame_stats: FrameStats::new(config.stats, vulkan.frame_sync.frames_in_flight())?,
             vulkan,
+            scene,
+            camera: Camera::looking_at(glam::Vec3::splat(2.0), glam::Vec3::ZERO),
+            instance_ranges: Vec::new(),
+            gpu_driven: true,
             current_frame: 0,
             framebuffer_resized,
         })
@@ -161,26 +306,62 @@ impl VulkanApp {
 }
 
 impl VulkanApp {
-    pub const WIDTH: u32 = 800;
-    pub const HEIGHT: u32 = 600;
-
     pub fn run(&mut self) {
         self.main_loop();
+        self.window_manager.save(&self.window);
+    }
+
+    fn create_scene() -> Scene {
+        let quad = MeshHandle(0);
+        let material = MaterialHandle(0);
+
+        let mut scene = Scene::default();
+        scene.add_object(quad, material, Transform::IDENTITY);
+        scene.add_object(
+            quad,
+            material,
+            Transform::from_translation_scale(glam::vec3(0.75, -0.75, -0.25), 0.5),
+        );
+        scene.add_object(
+            quad,
+            material,
+            Transform::from_translation_scale(glam::vec3(-0.75, 0.75, -0.25), 0.5),
+        );
+
+        let instance_count = INSTANCE_GRID_SIZE * INSTANCE_GRID_SIZE;
+        scene.add_instanced_object(
+            quad,
+            material,
+            Transform::from_translation_scale(glam::vec3(0.0, 0.0, -1.0), 3.0),
+            (0..instance_count)
+                .map(|i| Self::grid_instance(i, 0.0))
+                .collect(),
+        );
+
+        scene
+    }
+    fn grid_instance(index: u32, time: f32) -> InstanceData {
+        let x = (index % INSTANCE_GRID_SIZE) as f32 / INSTANCE_GRID_SIZE as f32 - 0.5;
+        let y = (index / INSTANCE_GRID_SIZE) as f32 / INSTANCE_GRID_SIZE as f32 - 0.5;
+        let height = 0.05 * (time * 2.0 + (x + y) * 4.0 * PI).sin();
+
+        let transform = glam::Mat4::from_scale_rotation_translation(
+            glam::Vec3::splat(0.8 / INSTANCE_GRID_SIZE as f32),
+            glam::Quat::IDENTITY,
+            glam::vec3(x, y, height),
+        );
+        let colour = glam::vec4(x + 0.5, y + 0.5, 1.0 - (x + y + 1.0) / 2.0, 1.0);
+        scene::instance(transform, colour)
     }
 
-    fn init_window() -> Result<(Glfw, PWindow, Arc<AtomicBool>)> {
-        let callback = |x, y| println!("Callback error while loading glfw: {x}, {y}");
+    fn init_window(
+        config: &WindowConfig,
+    ) -> Result<(Glfw, PWindow, WindowEvents, WindowManager, Arc<AtomicBool>)> {
+        let callback = |x, y| log::error!(target: target::WINDOW, "glfw error {x:?}: {y}");
         let mut glfw =
             glfw::init(callback).map_err(|e| err(&format!("Failed to initialise glfw: {e:?}")))?;
 
-        // Disable OpenGL since we want to use Vulkan
-        glfw.window_hint(WindowHint::ClientApi(ClientApiHint::NoApi));
-        // Disable resizing until we support recreating the swapchain
-        glfw.window_hint(WindowHint::Resizable(true));
-
-        let (mut window, _) = glfw
-            .create_window(Self::WIDTH, Self::HEIGHT, "Vulkan", WindowMode::Windowed)
-            .ok_or(err("Failed to create a window"))?;
+        let (mut window, events, window_manager) = WindowManager::create_window(&mut glfw, config)?;
 
         let framebuffer_resized = Arc::new(AtomicBool::new(false));
         {
@@ -190,84 +371,182 @@ impl VulkanApp {
             });
         }
 
-        Ok((glfw, window, framebuffer_resized))
+        // Events read by `Input`
+        window.set_key_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_cursor_pos_polling(true);
+        window.set_scroll_polling(true);
+        window.set_focus_polling(true);
+
+        Ok((glfw, window, events, window_manager, framebuffer_resized))
     }
-    fn init_vulkan(glfw: &Glfw, window: &glfw::Window) -> Result<VulkanData> {
+    fn init_vulkan(
+        glfw: &Glfw,
+        window: &glfw::Window,
+        swapchain_settings: SwapchainSettings,
+        config: &Config,
+    ) -> Result<VulkanData> {
+        let hdr_config = &config.display.hdr;
+        let frames_in_flight = config.frames.frames_in_flight()?;
+
         // TODO Consider safety arguments of dynamically loading the library, and maybe handle a failure with some nicer logs?
-        println!("Loading Vulkan library");
+        log::debug!(target: target::INSTANCE, "Loading Vulkan library");
         let entry = unsafe { ash::Entry::load()? };
         // let entry = ash::Entry::linked();
-        println!("Loaded Vulkan library");
+        let instance_version =
+            unsafe { entry.try_enumerate_instance_version() }?.unwrap_or(ash::vk::API_VERSION_1_0);
+        log::info!(
+            target: target::INSTANCE,
+            "Loaded Vulkan library, supporting instance version {}",
+            version_string(instance_version)
+        );
 
         if ENABLE_VALIDATION && !Self::check_validation_layer_support(&entry) {
             return error("Validation layers requested, but not available.");
         }
 
-        let instance = Self::create_instance(&entry, glfw)?;
-        let (debug_utils_instance, debug_callback) = Self::setup_debug_messenger(&entry, &instance);
+        let mut debug_messenger =
+            ENABLE_VALIDATION.then(|| DebugMessenger::new(&config.validation));
+        let instance = Self::create_instance(
+            &entry,
+            glfw,
+            swapchain_settings.hdr,
+            debug_messenger.as_ref(),
+        )?;
+        if let Some(debug_messenger) = &mut debug_messenger {
+            if let Err(e) = debug_messenger.register(&entry, &instance) {
+                log::warn!(target: target::INSTANCE, "Failed to create debug messenger: {e}");
+            }
+        }
+        let instance = InstanceOwner::new(entry, instance, debug_messenger);
 
-        let surface = Self::create_surface(&instance, window)?;
+        let surface = owned::Surface::new(&instance, Self::create_surface(&instance, window)?);
 
-        let surface_instance = ash::khr::surface::Instance::new(&entry, &instance);
+        let surface_instance = ash::khr::surface::Instance::new(instance.entry(), &instance);
         let (physical_device, device_properties) =
-            unsafe { Self::pick_physical_device(&instance, &surface_instance, surface)? };
+            unsafe { Self::pick_physical_device(&instance, &surface_instance, *surface)? };
         // Safety: the PhysicalDevice from `pick_physical_device` satisfies `is_device_suitable`
-        let (device, graphics_queue, present_queue) = unsafe {
-            Self::create_logical_device(&instance, &surface_instance, physical_device, surface)
+        let (device, graphics_queue, present_queue, enabled_features) = unsafe {
+            Self::create_logical_device(
+                &instance,
+                &surface_instance,
+                physical_device,
+                *surface,
+                swapchain_settings.hdr,
+                instance
+                    .debug_messenger()
+                    .is_some_and(DebugMessenger::instruments_shaders),
+                config.validation.debug_printf,
+            )
         }?;
+        let device = DeviceOwner::new(&instance, device);
+        let synchronization2_device = enabled_features
+            .synchronization2
+            .then(|| ash::khr::synchronization2::Device::new(&instance, &device));
+        let debug_utils = DebugUtils::new(&instance, &device, instance.debug_messenger().is_some());
+        let hdr_metadata_device = enabled_features
+            .hdr_metadata
+            .then(|| ash::ext::hdr_metadata::Device::new(&instance, &device));
 
         let swapchain_device = ash::khr::swapchain::Device::new(&instance, &device);
-        let (swapchain, swapchain_images, swapchain_format, swapchain_extent) = unsafe {
+        let (
+            swapchain,
+            swapchain_images,
+            swapchain_surface_format,
+            swapchain_extent,
+            swapchain_present_mode,
+        ) = unsafe {
             Self::create_swap_chain(
                 window,
                 &instance,
-                &surface_instance,
                 &swapchain_device,
                 physical_device,
-                surface,
+                &surface,
+                swapchain_settings,
+                ash::vk::SwapchainKHR::null(),
             )
         }?;
+        let swapchain = unsafe { Owned::new(&device, swapchain) };
+        let swapchain_format = swapchain_surface_format.format;
 
         let swapchain_image_views =
             Self::create_image_views(&device, &swapchain_images, swapchain_format)?;
+        let render_finished_semaphores =
+            Self::create_semaphores(&device, swapchain_images.len() as u32)?;
+        let images_in_flight = vec![None; swapchain_images.len()];
 
-        let render_pass = Self::create_render_pass(&device, swapchain_format)?;
+        let render_pass = Self::create_render_pass(&device, SCENE_FORMAT)?;
 
         let descriptor_set_layout = Self::create_descriptor_set_layout(&device)?;
+        let mut bindless = BindlessTextures::new(&device)?;
 
-        let (shader_module, pipeline_layout, graphics_pipeline) =
-            Self::create_graphics_pipeline(&device, render_pass, &descriptor_set_layout)?;
+        let (shader_module, pipeline_layout, graphics_pipeline) = Self::create_graphics_pipeline(
+            &device,
+            &device_properties.limits,
+            *render_pass,
+            &[*descriptor_set_layout, *bindless.descriptor_set_layout],
+        )?;
 
-        let swap_chain_framebuffers = Self::create_framebuffers(
+        let mut output_pass = OutputPass::new(
+            &device,
+            *shader_module,
+            swapchain_format,
+            hdr_config.paper_white_nits,
+        )?;
+        output_pass.create_targets(
+            &instance,
             &device,
+            physical_device,
+            *render_pass,
             &swapchain_image_views,
-            render_pass,
             swapchain_extent,
         )?;
 
+        let hdr_metadata = hdr_metadata_device.as_ref().map(|_| hdr_config.metadata());
+        if let (Some(hdr_metadata_device), Some(hdr_metadata)) =
+            (&hdr_metadata_device, hdr_metadata)
+        {
+            Self::set_hdr_metadata(
+                hdr_metadata_device,
+                *swapchain,
+                swapchain_surface_format,
+                hdr_metadata,
+            );
+        }
+
         let command_pool = Self::create_command_pool(
             &instance,
             &device,
             &surface_instance,
             physical_device,
-            surface,
+            *surface,
         )?;
 
         let (texture_image, texture_image_memory) = Self::create_texture_image(
             &instance,
             &device,
+            synchronization2_device.as_ref(),
             physical_device,
-            command_pool,
+            *command_pool,
             graphics_queue,
         )?;
 
-        let texture_image_view = Self::create_texture_image_view(&device, texture_image)?;
+        let texture_image_view = Self::create_texture_image_view(&device, *texture_image)?;
         let texture_sampler = Self::create_texture_sampler(&device, &device_properties.limits)?;
+        // Every object uses material 0 for now
+        let materials = vec![BindlessTexture {
+            image: bindless.add_image(
+                &device,
+                *texture_image_view,
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )?,
+            sampler: bindless.add_sampler(&device, *texture_sampler)?,
+        }];
 
         let (vertex_buffer, vertex_buffer_memory) = Self::create_vertex_buffer(
             &instance,
             &device,
-            command_pool,
+            *command_pool,
             graphics_queue,
             physical_device,
         )?;
@@ -275,47 +554,105 @@ impl VulkanApp {
         let (index_buffer, index_buffer_memory) = Self::create_index_buffer(
             &instance,
             &device,
-            command_pool,
+            *command_pool,
             graphics_queue,
             physical_device,
         )?;
 
         let (uniform_buffers, uniform_buffers_memory, uniform_buffers_mapped) =
-            Self::create_uniform_buffers(&instance, &device, physical_device)?;
+            Self::create_uniform_buffers(&instance, &device, physical_device, frames_in_flight)?;
 
-        let descriptor_pool = Self::create_descriptor_pool(&device)?;
-        let descriptor_sets = Self::create_descriptor_sets(
+        let (object_buffers, object_buffers_memory, object_buffers_mapped) =
+            Self::create_object_buffers(&instance, &device, physical_device, frames_in_flight)?;
+
+        let (instance_buffers, instance_buffers_memory, instance_buffers_mapped) =
+            Self::create_instance_buffers(&instance, &device, physical_device, frames_in_flight)?;
+
+        let meshes = vec![Mesh {
+            index_count: INDICES.len() as u32,
+            first_index: 0,
+            vertex_offset: 0,
+            // Corners of the quad are at (±0.5, ±0.5)
+            bounding_radius: 0.5 * SQRT_2,
+        }];
+
+        let gpu_culling = GpuCulling::new(
+            &instance,
             &device,
-            descriptor_set_layout,
-            descriptor_pool,
-            &uniform_buffers,
-            texture_image_view,
-            texture_sampler,
+            physical_device,
+            *shader_module,
+            frames_in_flight,
+            MAX_OBJECTS,
+            enabled_features.draw_indirect_count,
         )?;
 
-        let command_buffers = Self::create_command_buffers(&device, command_pool)?;
+        let frame_descriptors = (0..frames_in_flight)
+            .map(|_| DescriptorAllocator::new(&device, FRAME_POOL_INITIAL_SETS, &FRAME_POOL_RATIOS))
+            .collect();
+        let descriptor_sets = vec![ash::vk::DescriptorSet::null(); frames_in_flight as usize];
+
+        let command_buffers =
+            Self::create_command_buffers(&device, *command_pool, frames_in_flight)?;
+
+        let graphics_family = unsafe {
+            Self::find_queue_families(&instance, &surface_instance, physical_device, *surface)
+        }
+        .graphics_family
+        .unwrap();
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let gpu_timer = GpuTimer::new(
+            &device,
+            &device_properties.limits,
+            &queue_families[graphics_family as usize],
+            frames_in_flight,
+        )?;
+        let gpu_queries = GpuQueries::new(
+            &device,
+            enabled_features.pipeline_statistics_query,
+            frames_in_flight,
+            MAX_OBJECTS,
+        )?;
 
-        let (image_available_semaphores, render_finished_semaphores, in_flight_fences) =
-            Self::create_sync_objects(&device)?;
+        let image_available_semaphores = Self::create_semaphores(&device, frames_in_flight)?;
+        let timeline_semaphores =
+            config.frames.timeline_semaphores && enabled_features.timeline_semaphore;
+        if config.frames.timeline_semaphores && !timeline_semaphores {
+            log::warn!(
+                target: target::FRAME,
+                "Timeline semaphores requested, but not supported. Falling back to fences"
+            );
+        }
+        let frame_sync = FrameSync::new(&device, frames_in_flight, timeline_semaphores)?;
+        log::info!(
+            target: target::FRAME,
+            "Pacing {frames_in_flight} frames in flight with {}",
+            if frame_sync.uses_timeline() { "a timeline semaphore" } else { "fences" }
+        );
 
-        Ok(VulkanData {
-            entry,
+        let vulkan = VulkanData {
             instance,
-            debug_utils_instance,
-            debug_callback,
+            debug_utils,
             surface,
-            surface_instance,
             physical_device,
+            device_limits: device_properties.limits,
             device,
             graphics_queue,
             present_queue,
             swapchain_device,
             swapchain,
             swapchain_images,
-            swapchain_format,
+            swapchain_surface_format,
             swapchain_extent,
+            swapchain_present_mode,
             swapchain_image_views,
-            swap_chain_framebuffers,
+            render_finished_semaphores,
+            images_in_flight,
+            hdr_metadata_device,
+            hdr_metadata,
+            output_pass,
+            gpu_timer,
+            gpu_queries,
             shader_module,
             render_pass,
             descriptor_set_layout,
@@ -326,6 +663,8 @@ impl VulkanApp {
             texture_image_memory,
             texture_image_view,
             texture_sampler,
+            bindless,
+            materials,
             vertex_buffer,
             vertex_buffer_memory,
             index_buffer,
@@ -333,46 +672,110 @@ impl VulkanApp {
             uniform_buffers,
             uniform_buffers_memory,
             uniform_buffers_mapped,
-            descriptor_pool,
+            object_buffers,
+            object_buffers_memory,
+            object_buffers_mapped,
+            instance_buffers,
+            instance_buffers_memory,
+            instance_buffers_mapped,
+            meshes,
+            gpu_culling,
+            frame_descriptors,
             descriptor_sets,
             command_buffers,
             image_available_semaphores,
-            render_finished_semaphores,
-            in_flight_fences,
-        })
+            frame_sync,
+            deletion_queue: DeletionQueue::new(frames_in_flight),
+        };
+        vulkan.name_objects();
+        vulkan.name_swapchain_objects();
+        vulkan.add_entry_points();
+
+        Ok(vulkan)
     }
 
     fn main_loop(&mut self) {
-        const FRAME_COUNT: u32 = 10000;
-
-        let mut x = 0;
-        let mut i = Instant::now();
-        let mut times = Vec::with_capacity(FRAME_COUNT as usize);
+        let mut last_update = Instant::now();
 
         while !self.window.should_close() {
+            self.input.begin_frame();
             self.glfw.poll_events();
+            for (_, event) in glfw::flush_messages(&self.events) {
+                self.window_manager.handle_event(&event);
+                self.input.handle_event(&event);
+            }
+            self.input.poll_gamepad(&self.glfw);
+
+            if self.input.pressed(bindings::CLOSE) {
+                self.window.set_should_close(true);
+            }
+            if self.input.pressed(bindings::CYCLE_PRESENT_MODE) {
+                let present_mode = self.swapchain_settings.present_mode.next();
+                self.set_present_mode(present_mode).unwrap();
+            }
+            if self.input.pressed(bindings::CYCLE_WINDOW_MODE) {
+                let mode = self.window_manager.mode().next();
+                self.window_manager
+                    .set_mode(&mut self.glfw, &mut self.window, mode);
+            }
+            if self.input.pressed(bindings::TOGGLE_GPU_CULLING) {
+                self.gpu_driven = !self.gpu_driven;
+                log::info!(
+                    target: target::FRAME,
+                    "GPU culling and indirect draws: {}",
+                    self.gpu_driven
+                );
+            }
+            if self.input.pressed(bindings::PRINT_GPU_QUERIES) {
+                self.log_gpu_queries();
+            }
+            if self.input.pressed(bindings::NEXT_MONITOR) {
+                self.window_manager
+                    .next_monitor(&mut self.glfw, &mut self.window);
+            }
+
+            let now = Instant::now();
+            self.camera
+                .update(&self.input, (now - last_update).as_secs_f32());
+            last_update = now;
+
+            let cursor_mode = if self.camera.captures_cursor(&self.input) {
+                CursorMode::Disabled
+            } else {
+                CursorMode::Normal
+            };
+            if self.window.get_cursor_mode() != cursor_mode {
+                self.window.set_cursor_mode(cursor_mode);
+            }
+
             self.draw_frame().unwrap();
 
-            let elapsed = i.elapsed();
-            times.push(elapsed);
-            i = Instant::now();
-            x += 1;
-            if x == FRAME_COUNT {
-                println!("This has gone on long enough!");
-                break;
+            let validation_error = self
+                .vulkan
+                .instance
+                .debug_messenger()
+                .and_then(DebugMessenger::take_error);
+            if let Some(error) = validation_error {
+                panic!("Validation error with `panic_on_error` set: {error}");
             }
-            
-            sleep(Duration::from_millis(1));
-        }
 
-        let mean_frame_time = times.iter().sum::<Duration>() / FRAME_COUNT;
-        println!("Average frame time is {}us", mean_frame_time.as_micros());
-        let fps = 1.0 / mean_frame_time.as_secs_f64();
-        println!("That's {fps}fps");
+            self.frame_stats.end_frame();
+            if self.frame_stats.benchmark_finished() {
+                log::info!(target: target::FRAME, "Benchmark finished");
+                self.window.set_should_close(true);
+            }
+
+            self.frame_limiter.wait();
+        }
 
-        let max = times.iter().max().unwrap();
-        let min = times.iter().min().unwrap();
-        println!("Min: {}us, max: {}us", min.as_micros(), max.as_micros());
+        self.frame_stats.report();
+        if let Some(counts) = self.validation_counts() {
+            log::info!(
+                target: target::VALIDATION,
+                "{} errors, {} warnings",
+                counts.errors, counts.warnings
+            );
+        }
 
         unsafe {
             _ = self.vulkan.device.device_wait_idle();
@@ -381,12 +784,36 @@ impl VulkanApp {
 }
 
 impl VulkanApp {
-    fn create_instance(entry: &ash::Entry, glfw: &Glfw) -> Result<ash::Instance> {
-        let extensions = Self::get_required_extensions(glfw);
+    fn create_instance(
+        entry: &ash::Entry,
+        glfw: &Glfw,
+        hdr: bool,
+        debug_messenger: Option<&DebugMessenger>,
+    ) -> Result<ash::Instance> {
+        let mut extensions = Self::get_required_extensions(entry, glfw, hdr);
+
+        let validation_features =
+            debug_messenger.map_or(&[][..], DebugMessenger::validation_features);
+        let validation_features_available =
+            !validation_features.is_empty() && Self::check_validation_features_support(entry);
+        if validation_features_available {
+            extensions.push(
+                ash::ext::validation_features::NAME
+                    .to_str()
+                    .expect("Extension names are valid UTF-8")
+                    .to_string(),
+            );
+        } else if !validation_features.is_empty() {
+            log::warn!(
+                target: target::INSTANCE,
+                "Validation features requested, but VK_EXT_validation_features isn't available"
+            );
+        }
+
         let extension_names = extensions
-            .into_iter()
+            .iter()
             .map(|name| {
-                ffi::CString::new(name.into_bytes())
+                ffi::CString::new(name.as_str())
                     .expect("Extension names should not contain null bytes")
             })
             .collect::<Vec<_>>();
@@ -406,44 +833,68 @@ impl VulkanApp {
             .application_info(&application_info)
             .enabled_extension_names(&extension_names);
 
-        // Place this outside the `if` to ensure it doesn't get dropped early
-        let mut debug_info = Self::get_create_debug_info();
-        let create_info = if ENABLE_VALIDATION {
+        // Place this outside the `if` to ensure it doesn't get dropped early. This reports
+        //  messages from creating and destroying the instance, which the messenger can't
+        let mut debug_info = debug_messenger.map(DebugMessenger::create_info);
+        let create_info = if let Some(debug_info) = &mut debug_info {
             create_info
                 .enabled_layer_names(VALIDATION_LAYERS)
-                .push_next(&mut debug_info)
+                .push_next(debug_info)
+        } else {
+            create_info
+        };
+        let mut validation_features_info = ash::vk::ValidationFeaturesEXT::default()
+            .enabled_validation_features(validation_features);
+        let create_info = if validation_features_available {
+            create_info.push_next(&mut validation_features_info)
         } else {
             create_info
         };
 
         let instance = unsafe { entry.create_instance(&create_info, None)? };
+        log::info!(
+            target: target::INSTANCE,
+            "Created instance with extensions {extensions:?}{}",
+            if debug_messenger.is_some() {
+                " and validation layers"
+            } else {
+                ""
+            }
+        );
+        if !validation_features.is_empty() {
+            log::info!(
+                target: target::INSTANCE,
+                "Validation features: {validation_features:?}"
+            );
+        }
 
         Ok(instance)
     }
-    fn setup_debug_messenger(
-        entry: &ash::Entry,
-        instance: &ash::Instance,
-    ) -> (
-        Option<ash::ext::debug_utils::Instance>,
-        Option<ash::vk::DebugUtilsMessengerEXT>,
-    ) {
-        if ENABLE_VALIDATION {
-            let debug_utils_instance = ash::ext::debug_utils::Instance::new(entry, instance);
-            let debug_messenger = match Self::create_debug_callback(&debug_utils_instance) {
-                Ok(x) => Some(x),
-                Err(e) => {
-                    println!("Failed to create debug utils with error: {e}");
-                    None
-                }
-            };
-            (Some(debug_utils_instance), debug_messenger)
-        } else {
-            (None, None)
-        }
-    }
-    fn get_required_extensions(glfw: &Glfw) -> Vec<String> {
+    fn get_required_extensions(entry: &ash::Entry, glfw: &Glfw, hdr: bool) -> Vec<String> {
         let mut extensions = glfw.get_required_instance_extensions().unwrap_or_default();
 
+        // Needed for surfaces to report HDR colour spaces, but optional so SDR still works
+        if hdr {
+            let available_extensions =
+                unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap_or_default();
+            let colour_space_available = available_extensions.iter().any(|available| {
+                available.extension_name_as_c_str() == Ok(ash::ext::swapchain_colorspace::NAME)
+            });
+            if colour_space_available {
+                extensions.push(
+                    ash::ext::swapchain_colorspace::NAME
+                        .to_str()
+                        .expect("Extension names are valid UTF-8")
+                        .to_string(),
+                );
+            } else {
+                log::warn!(
+                    target: target::INSTANCE,
+                    "HDR requested, but VK_EXT_swapchain_colorspace isn't available"
+                );
+            }
+        }
+
         if ENABLE_VALIDATION {
             extensions.push(
                 ash::ext::debug_utils::NAME
@@ -460,46 +911,25 @@ impl VulkanApp {
             return false;
         };
 
-        let res = VALIDATION_LAYERS.iter().all(|&layer_name| {
+        VALIDATION_LAYERS.iter().all(|&layer_name| {
             layers.iter().any(|layer| unsafe {
                 ffi::CStr::from_ptr(layer.layer_name.as_ptr()) == ffi::CStr::from_ptr(layer_name)
             })
-        });
-        println!("{res}");
-        res
-    }
-    fn get_create_debug_info<'a>() -> ash::vk::DebugUtilsMessengerCreateInfoEXT<'a> {
-        let callback: Box<DebugCallback> = Box::new(|message_severity, message_type, data| {
-            let message = unsafe { data.message_as_c_str() }
-                .and_then(|x| x.to_str().ok())
-                .unwrap_or_default();
-            println!("{message_severity:?}-{message_type:?}: {message}");
-        });
-
-        let info = ash::vk::DebugUtilsMessengerCreateInfoEXT::default()
-            .message_severity(
-                ash::vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                    | ash::vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                    | ash::vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                    | ash::vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
-            )
-            .message_type(
-                ash::vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                    | ash::vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
-                    | ash::vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
-            )
-            .user_data(Box::into_raw(callback) as _)
-            .pfn_user_callback(Some(debug_callback));
-
-        info
+        })
     }
-    fn create_debug_callback(
-        debug_utils_instance: &ash::ext::debug_utils::Instance,
-    ) -> Result<ash::vk::DebugUtilsMessengerEXT> {
-        let create_info = Self::get_create_debug_info();
-        let messenger =
-            unsafe { debug_utils_instance.create_debug_utils_messenger(&create_info, None)? };
-        Ok(messenger)
+    /// Whether the validation layer provides `VK_EXT_validation_features`
+    fn check_validation_features_support(entry: &ash::Entry) -> bool {
+        let Ok(extensions) = (unsafe {
+            entry.enumerate_instance_extension_properties(Some(ffi::CStr::from_ptr(
+                VALIDATION_LAYERS[0],
+            )))
+        }) else {
+            return false;
+        };
+
+        extensions.iter().any(|extension| {
+            extension.extension_name_as_c_str() == Ok(ash::ext::validation_features::NAME)
+        })
     }
     fn create_surface(
         instance: &ash::Instance,
@@ -532,12 +962,25 @@ impl VulkanApp {
                 .find(|&x| Self::is_device_suitable(instance, surface_instance, x, surface))
         };
 
-        device
+        let (device, properties) = device
             .map(|d| {
                 let x = unsafe { instance.get_physical_device_properties(d) };
                 (d, x)
             })
-            .ok_or_else(|| err("No suitable device found").into())
+            .ok_or_else(|| err("No suitable device found"))?;
+
+        log::info!(
+            target: target::DEVICE,
+            "Using {} ({:?}), Vulkan {}, driver version {:#x}",
+            properties
+                .device_name_as_c_str()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            properties.device_type,
+            version_string(properties.api_version),
+            properties.driver_version
+        );
+        Ok((device, properties))
     }
     /// # SAFETY
     /// - `device` MUST be a valid `VkPhysicalDevice` handle
@@ -552,9 +995,11 @@ impl VulkanApp {
         let device_properties = unsafe { instance.get_physical_device_properties(device) };
         let device_features = unsafe { instance.get_physical_device_features(device) };
 
+        let mut features11 = ash::vk::PhysicalDeviceVulkan11Features::default();
         let mut features12 = ash::vk::PhysicalDeviceVulkan12Features::default();
-        let mut device_features2 =
-            ash::vk::PhysicalDeviceFeatures2::default().push_next(&mut features12);
+        let mut device_features2 = ash::vk::PhysicalDeviceFeatures2::default()
+            .push_next(&mut features11)
+            .push_next(&mut features12);
         unsafe { instance.get_physical_device_features2(device, &mut device_features2) };
 
         // No features needed for now, left here for future reference to use them though
@@ -599,6 +1044,10 @@ impl VulkanApp {
             && swap_chain_adequate
             && features12.vulkan_memory_model == ash::vk::TRUE // RustGPU shaders seem to need this
             && device_features.sampler_anisotropy == ash::vk::TRUE // Anisotropy used in sampling shaders
+            && device_features.multi_draw_indirect == ash::vk::TRUE // GPU culling draws every object in one call
+            && features11.shader_draw_parameters == ash::vk::TRUE // Indirect draws find their object through `draw_index`
+            && device_features.shader_sampled_image_array_dynamic_indexing == ash::vk::TRUE // Each draw picks its texture by index
+            && BindlessTextures::supported(&features12) // Textures are sampled from one partially bound array
     }
     /// # SAFETY
     /// - `device` MUST be a valid `VkPhysicalDevice` handle
@@ -661,7 +1110,10 @@ impl VulkanApp {
         surface_instance: &ash::khr::surface::Instance,
         physical_device: ash::vk::PhysicalDevice,
         surface: ash::vk::SurfaceKHR,
-    ) -> Result<(ash::Device, ash::vk::Queue, ash::vk::Queue)> {
+        hdr: bool,
+        shader_instrumentation: bool,
+        debug_printf: bool,
+    ) -> Result<(ash::Device, ash::vk::Queue, ash::vk::Queue, EnabledFeatures)> {
         // Safety: `physical_device` is a valid VkPhysicalDevice handle
         let indices = unsafe {
             Self::find_queue_families(instance, surface_instance, physical_device, surface)
@@ -683,19 +1135,96 @@ impl VulkanApp {
             })
             .collect::<Vec<_>>();
 
-        let device_features = ash::vk::PhysicalDeviceFeatures::default().sampler_anisotropy(true);
-
-        let extensions = DEVICE_EXTENSIONS
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let pipeline_statistics_query =
+            supported_features.pipeline_statistics_query == ash::vk::TRUE;
+        // GPU-assisted validation and debug printf write their results from every shader stage
+        let stores_and_atomics = |supported| shader_instrumentation && supported == ash::vk::TRUE;
+        let device_features = ash::vk::PhysicalDeviceFeatures::default()
+            .sampler_anisotropy(true)
+            .multi_draw_indirect(true)
+            .shader_sampled_image_array_dynamic_indexing(true)
+            .pipeline_statistics_query(pipeline_statistics_query)
+            .vertex_pipeline_stores_and_atomics(stores_and_atomics(
+                supported_features.vertex_pipeline_stores_and_atomics,
+            ))
+            .fragment_stores_and_atomics(stores_and_atomics(
+                supported_features.fragment_stores_and_atomics,
+            ));
+
+        let mut extensions = DEVICE_EXTENSIONS
             .iter()
             .map(|x| x.as_ptr())
             .collect::<Vec<_>>();
 
-        let mut x = ash::vk::PhysicalDeviceVulkan12Features::default().vulkan_memory_model(true);
+        // synchronization2 isn't core until Vulkan 1.3, so only use it where the driver has it
+        let synchronization2 =
+            unsafe { Self::supports_synchronization2(instance, physical_device) };
+        if synchronization2 {
+            extensions.push(ash::khr::synchronization2::NAME.as_ptr());
+        }
+
+        let hdr_metadata = hdr
+            && unsafe {
+                Self::supports_device_extension(
+                    instance,
+                    physical_device,
+                    ash::ext::hdr_metadata::NAME,
+                )
+            };
+        if hdr_metadata {
+            extensions.push(ash::ext::hdr_metadata::NAME.as_ptr());
+        }
+
+        // Shaders built for `debug_printf!` declare SPV_KHR_non_semantic_info, which needs this.
+        //  build.rs only declares it when told to, alongside setting `[validation] debug_printf`
+        let shader_non_semantic_info = debug_printf
+            && unsafe {
+                Self::supports_device_extension(
+                    instance,
+                    physical_device,
+                    ash::khr::shader_non_semantic_info::NAME,
+                )
+            };
+        if shader_non_semantic_info {
+            extensions.push(ash::khr::shader_non_semantic_info::NAME.as_ptr());
+        }
+
+        let supported12 = {
+            let mut features12 = ash::vk::PhysicalDeviceVulkan12Features::default();
+            let mut device_features2 =
+                ash::vk::PhysicalDeviceFeatures2::default().push_next(&mut features12);
+            unsafe {
+                instance.get_physical_device_features2(physical_device, &mut device_features2)
+            };
+            features12
+        };
+        // Without `vkCmdDrawIndexedIndirectCount`, GPU culling falls back to drawing every slot
+        let draw_indirect_count = supported12.draw_indirect_count == ash::vk::TRUE;
+        // Without timeline semaphores, frames are paced with a fence each
+        let timeline_semaphore = supported12.timeline_semaphore == ash::vk::TRUE;
+
+        let mut features11 =
+            ash::vk::PhysicalDeviceVulkan11Features::default().shader_draw_parameters(true);
+        let mut x = BindlessTextures::enable_features(
+            ash::vk::PhysicalDeviceVulkan12Features::default()
+                .vulkan_memory_model(true)
+                .draw_indirect_count(draw_indirect_count)
+                .timeline_semaphore(timeline_semaphore),
+        );
+        let mut synchronization2_features =
+            ash::vk::PhysicalDeviceSynchronization2Features::default().synchronization2(true);
         let create_info = ash::vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_create_infos)
             .enabled_features(&device_features)
             .enabled_extension_names(&extensions)
+            .push_next(&mut features11)
             .push_next(&mut x);
+        let create_info = if synchronization2 {
+            create_info.push_next(&mut synchronization2_features)
+        } else {
+            create_info
+        };
 
         let device = unsafe { instance.create_device(physical_device, &create_info, None) }?;
 
@@ -703,7 +1232,66 @@ impl VulkanApp {
             unsafe { device.get_device_queue(indices.graphics_family.unwrap(), 0) };
         let present_queue = unsafe { device.get_device_queue(indices.present_family.unwrap(), 0) };
 
-        Ok((device, graphics_queue, present_queue))
+        let enabled_features = EnabledFeatures {
+            synchronization2,
+            draw_indirect_count,
+            timeline_semaphore,
+            hdr_metadata,
+            pipeline_statistics_query,
+        };
+        log::info!(
+            target: target::DEVICE,
+            "Created device with extensions {:?}",
+            extensions
+                .iter()
+                .map(|&name| unsafe { ffi::CStr::from_ptr(name) })
+                .collect::<Vec<_>>()
+        );
+        log::info!(target: target::DEVICE, "Optional features: {enabled_features:?}");
+
+        Ok((device, graphics_queue, present_queue, enabled_features))
+    }
+    /// # Safety
+    /// - `physical_device` MUST be a valid `VkPhysicalDevice` handle
+    unsafe fn supports_synchronization2(
+        instance: &ash::Instance,
+        physical_device: ash::vk::PhysicalDevice,
+    ) -> bool {
+        let extension_available = unsafe {
+            Self::supports_device_extension(
+                instance,
+                physical_device,
+                ash::khr::synchronization2::NAME,
+            )
+        };
+        if !extension_available {
+            return false;
+        }
+
+        let mut synchronization2_features =
+            ash::vk::PhysicalDeviceSynchronization2Features::default();
+        let mut device_features2 =
+            ash::vk::PhysicalDeviceFeatures2::default().push_next(&mut synchronization2_features);
+        unsafe { instance.get_physical_device_features2(physical_device, &mut device_features2) };
+
+        synchronization2_features.synchronization2 == ash::vk::TRUE
+    }
+    /// # Safety
+    /// - `physical_device` MUST be a valid `VkPhysicalDevice` handle
+    unsafe fn supports_device_extension(
+        instance: &ash::Instance,
+        physical_device: ash::vk::PhysicalDevice,
+        extension: &ffi::CStr,
+    ) -> bool {
+        let Ok(available_extensions) =
+            (unsafe { instance.enumerate_device_extension_properties(physical_device) })
+        else {
+            return false;
+        };
+        available_extensions.iter().any(|available| {
+            // Safety: extension name is valid null-terminated utf-8 string
+            extension == unsafe { ffi::CStr::from_ptr(available.extension_name.as_ptr()) }
+        })
     }
     /// # Safety
     /// - `device` must a valid `VkPhysicalDevice` handle
@@ -729,7 +1317,39 @@ impl VulkanApp {
     }
     fn choose_swap_surface_format(
         available_formats: &[ash::vk::SurfaceFormatKHR],
+        hdr: bool,
     ) -> ash::vk::SurfaceFormatKHR {
+        // In order of preference: HDR10 is the common HDR display signal, and scRGB is mostly
+        //  found on Windows. Both need `VK_EXT_swapchain_colorspace` to be reported
+        const HDR_FORMATS: &[(ash::vk::Format, ash::vk::ColorSpaceKHR)] = &[
+            (
+                ash::vk::Format::A2B10G10R10_UNORM_PACK32,
+                ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+            ),
+            (
+                ash::vk::Format::A2R10G10B10_UNORM_PACK32,
+                ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+            ),
+            (
+                ash::vk::Format::R16G16B16A16_SFLOAT,
+                ash::vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+            ),
+        ];
+        if hdr {
+            for &(format, color_space) in HDR_FORMATS {
+                if let Some(available_format) = available_formats
+                    .iter()
+                    .find(|x| x.format == format && x.color_space == color_space)
+                {
+                    return *available_format;
+                }
+            }
+            log::warn!(
+                target: target::SWAPCHAIN,
+                "HDR requested, but the surface has no HDR formats"
+            );
+        }
+
         for available_format in available_formats {
             if available_format.format == ash::vk::Format::B8G8R8A8_SRGB
                 && available_format.color_space == ash::vk::ColorSpaceKHR::SRGB_NONLINEAR
@@ -743,12 +1363,23 @@ impl VulkanApp {
     }
     fn choose_swap_present_mode(
         available_present_modes: &[ash::vk::PresentModeKHR],
+        requested: PresentMode,
     ) -> ash::vk::PresentModeKHR {
-        if available_present_modes.contains(&ash::vk::PresentModeKHR::MAILBOX) {
-            ash::vk::PresentModeKHR::MAILBOX
-        } else {
-            ash::vk::PresentModeKHR::FIFO
+        // FIFO is required to be supported, so is always a safe fallback
+        let present_mode = requested
+            .candidates()
+            .iter()
+            .copied()
+            .find(|mode| available_present_modes.contains(mode))
+            .unwrap_or(ash::vk::PresentModeKHR::FIFO);
+
+        if present_mode != requested.candidates()[0] {
+            log::warn!(
+                target: target::SWAPCHAIN,
+                "Present mode {requested:?} isn't supported, falling back to {present_mode:?}"
+            );
         }
+        present_mode
     }
     fn choose_swap_extent(
         window: &glfw::Window,
@@ -771,28 +1402,39 @@ impl VulkanApp {
             ash::vk::Extent2D { width, height }
         }
     }
+    /// Create a swapchain for `surface`, replacing `old_swapchain` if it isn't null. The old
+    /// swapchain is retired, and can only present images it's already acquired.
+    ///
     /// # Safety
     /// - `physical_device` must a valid `VkPhysicalDevice` handle
-    /// - `surface` must a valid `VkSurfaceKHR` handle
+    /// - `old_swapchain` must be null, or the current swapchain of `surface`
     /// - more conditions: TODO
     unsafe fn create_swap_chain(
         window: &glfw::Window,
         instance: &ash::Instance,
-        surface_instance: &ash::khr::surface::Instance,
         swapchain_device: &ash::khr::swapchain::Device,
         physical_device: ash::vk::PhysicalDevice,
-        surface: ash::vk::SurfaceKHR,
+        surface: &owned::Surface,
+        settings: SwapchainSettings,
+        old_swapchain: ash::vk::SwapchainKHR,
     ) -> Result<(
         ash::vk::SwapchainKHR,
         Vec<ash::vk::Image>,
-        ash::vk::Format,
+        ash::vk::SurfaceFormatKHR,
         ash::vk::Extent2D,
+        ash::vk::PresentModeKHR,
     )> {
+        let surface_instance = surface.surface_instance();
+        let surface = **surface;
         let swap_chain_support =
             unsafe { Self::query_swap_chain_support(surface_instance, physical_device, surface) }?;
 
-        let surface_format = Self::choose_swap_surface_format(&swap_chain_support.formats);
-        let present_mode = Self::choose_swap_present_mode(&swap_chain_support.present_modes);
+        let surface_format =
+            Self::choose_swap_surface_format(&swap_chain_support.formats, settings.hdr);
+        let present_mode = Self::choose_swap_present_mode(
+            &swap_chain_support.present_modes,
+            settings.present_mode,
+        );
         let extent = Self::choose_swap_extent(window, &swap_chain_support.capabilities);
 
         let mut image_count = swap_chain_support.capabilities.min_image_count + 1;
@@ -834,21 +1476,30 @@ impl VulkanApp {
             .composite_alpha(ash::vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(present_mode)
             .clipped(true)
-            .old_swapchain(ash::vk::SwapchainKHR::null());
+            .old_swapchain(old_swapchain);
 
         // Safety: TODO...
         let swapchain = unsafe { swapchain_device.create_swapchain(&create_info, None) }
             .map_err(|e| err(&format!("Failed to create swapchain: {}", e)))?;
 
         let images = unsafe { swapchain_device.get_swapchain_images(swapchain) }?;
+        log::debug!(
+            target: target::SWAPCHAIN,
+            "Created swapchain of {} {}x{} images, {:?} in {:?}, presenting with {present_mode:?}",
+            images.len(),
+            extent.width,
+            extent.height,
+            surface_format.format,
+            surface_format.color_space
+        );
 
-        Ok((swapchain, images, surface_format.format, extent))
+        Ok((swapchain, images, surface_format, extent, present_mode))
     }
-    fn create_image_view(
-        device: &ash::Device,
+    pub(crate) fn create_image_view(
+        device: &owned::Device,
         image: ash::vk::Image,
         format: ash::vk::Format,
-    ) -> Result<ash::vk::ImageView> {
+    ) -> Result<Owned<ash::vk::ImageView>> {
         let create_info = ash::vk::ImageViewCreateInfo::default()
             .image(image)
             .view_type(ash::vk::ImageViewType::TYPE_2D)
@@ -863,33 +1514,34 @@ impl VulkanApp {
             );
 
         let image_view = unsafe { device.create_image_view(&create_info, None) }?;
-        Ok(image_view)
+        Ok(unsafe { Owned::new(device, image_view) })
     }
     fn create_image_views(
-        device: &ash::Device,
+        device: &owned::Device,
         swap_chain_images: &[ash::vk::Image],
         swap_chain_image_format: ash::vk::Format,
-    ) -> Result<Vec<ash::vk::ImageView>> {
-        let image_views: Vec<ash::vk::ImageView> = swap_chain_images
+    ) -> Result<Vec<Owned<ash::vk::ImageView>>> {
+        let image_views: Vec<_> = swap_chain_images
             .iter()
             .flat_map(|image| Self::create_image_view(device, *image, swap_chain_image_format))
             .collect();
 
         Ok(image_views)
     }
+    /// The pass drawing the scene into a linear colour target, which is then read by the output pass
     fn create_render_pass(
-        device: &ash::Device,
-        swapchain_image_format: ash::vk::Format,
-    ) -> Result<ash::vk::RenderPass> {
+        device: &owned::Device,
+        colour_format: ash::vk::Format,
+    ) -> Result<Owned<ash::vk::RenderPass>> {
         let colour_attachment = ash::vk::AttachmentDescription::default()
-            .format(swapchain_image_format)
+            .format(colour_format)
             .samples(ash::vk::SampleCountFlags::TYPE_1)
             .load_op(ash::vk::AttachmentLoadOp::CLEAR)
             .store_op(ash::vk::AttachmentStoreOp::STORE)
             .stencil_load_op(ash::vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(ash::vk::AttachmentStoreOp::DONT_CARE)
             .initial_layout(ash::vk::ImageLayout::UNDEFINED)
-            .final_layout(ash::vk::ImageLayout::PRESENT_SRC_KHR);
+            .final_layout(ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
 
         let attachment_ref = ash::vk::AttachmentReference::default()
             .attachment(0)
@@ -900,45 +1552,73 @@ impl VulkanApp {
             .pipeline_bind_point(ash::vk::PipelineBindPoint::GRAPHICS)
             .color_attachments(&attachment_refs);
 
+        // The scene image is shared between frames in flight, so wait for the previous frame's
+        //  output pass to finish reading it (and its scene pass writing it) before clearing it
         let dependency = ash::vk::SubpassDependency::default()
             .src_subpass(ash::vk::SUBPASS_EXTERNAL)
             .dst_subpass(0)
-            .src_stage_mask(ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .src_access_mask(ash::vk::AccessFlags::empty())
+            .src_stage_mask(
+                ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | ash::vk::PipelineStageFlags::FRAGMENT_SHADER,
+            )
+            .src_access_mask(ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
             .dst_stage_mask(ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
             .dst_access_mask(ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+        // Make the finished scene visible to the output pass' fragment shader
+        let output_dependency = ash::vk::SubpassDependency::default()
+            .src_subpass(0)
+            .dst_subpass(ash::vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_stage_mask(ash::vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_access_mask(ash::vk::AccessFlags::SHADER_READ);
 
         let subpasses = [subpass];
         let attachments = [colour_attachment];
-        let dependencies = [dependency];
+        let dependencies = [dependency, output_dependency];
         let render_pass_info = ash::vk::RenderPassCreateInfo::default()
             .attachments(&attachments)
             .subpasses(&subpasses)
             .dependencies(&dependencies);
 
         let render_pass = unsafe { device.create_render_pass(&render_pass_info, None) }?;
-        Ok(render_pass)
+        Ok(unsafe { Owned::new(device, render_pass) })
     }
-    fn create_descriptor_set_layout(device: &ash::Device) -> Result<ash::vk::DescriptorSetLayout> {
+    fn create_descriptor_set_layout(
+        device: &owned::Device,
+    ) -> Result<Owned<ash::vk::DescriptorSetLayout>> {
         let ubo_layout_binding = ash::vk::DescriptorSetLayoutBinding::default()
             .binding(0)
             .descriptor_type(ash::vk::DescriptorType::UNIFORM_BUFFER)
             .descriptor_count(1)
             .stage_flags(ash::vk::ShaderStageFlags::VERTEX);
 
-        let sampler_layout_binding = ash::vk::DescriptorSetLayoutBinding::default()
-            .binding(1)
+        let objects_layout_binding = ash::vk::DescriptorSetLayoutBinding::default()
+            .binding(2)
             .descriptor_count(1)
-            .descriptor_type(ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .stage_flags(ash::vk::ShaderStageFlags::FRAGMENT);
+            .descriptor_type(ash::vk::DescriptorType::STORAGE_BUFFER)
+            .stage_flags(ash::vk::ShaderStageFlags::VERTEX);
+
+        let draw_object_indices_layout_binding = ash::vk::DescriptorSetLayoutBinding::default()
+            .binding(3)
+            .descriptor_count(1)
+            .descriptor_type(ash::vk::DescriptorType::STORAGE_BUFFER)
+            .stage_flags(ash::vk::ShaderStageFlags::VERTEX);
 
-        let bindings = [ubo_layout_binding, sampler_layout_binding];
+        let bindings = [
+            ubo_layout_binding,
+            objects_layout_binding,
+            draw_object_indices_layout_binding,
+        ];
 
         let layout_info = ash::vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
         let layout = unsafe { device.create_descriptor_set_layout(&layout_info, None) }?;
-        Ok(layout)
+        Ok(unsafe { Owned::new(device, layout) })
     }
-    fn create_shader_module(device: &ash::Device, code: &[u8]) -> Result<ash::vk::ShaderModule> {
+    fn create_shader_module(
+        device: &owned::Device,
+        code: &[u8],
+    ) -> Result<Owned<ash::vk::ShaderModule>> {
         let create_info = ash::vk::ShaderModuleCreateInfo {
             code_size: code.len(),
             p_code: code.as_ptr() as _,
@@ -946,33 +1626,42 @@ impl VulkanApp {
         };
 
         let shader_module = unsafe { device.create_shader_module(&create_info, None) }?;
-        Ok(shader_module)
+        Ok(unsafe { Owned::new(device, shader_module) })
     }
     fn create_graphics_pipeline(
-        device: &ash::Device,
+        device: &owned::Device,
+        device_limits: &ash::vk::PhysicalDeviceLimits,
         render_pass: ash::vk::RenderPass,
-        descriptor_set_layout: &ash::vk::DescriptorSetLayout,
+        descriptor_set_layouts: &[ash::vk::DescriptorSetLayout],
     ) -> Result<(
-        ash::vk::ShaderModule,
-        ash::vk::PipelineLayout,
-        ash::vk::Pipeline,
+        Owned<ash::vk::ShaderModule>,
+        Owned<ash::vk::PipelineLayout>,
+        Owned<ash::vk::Pipeline>,
     )> {
         const SHADER: &[u8] = include_bytes!(env!("shaders.spv"));
         let shader_module = Self::create_shader_module(device, SHADER)?;
+        log::debug!(target: target::ASSETS, "Loaded {} bytes of shaders", SHADER.len());
 
         let vert_shader_stage_info = ash::vk::PipelineShaderStageCreateInfo::default()
             .stage(ash::vk::ShaderStageFlags::VERTEX)
-            .module(shader_module)
+            .module(*shader_module)
             .name(c"main_vs");
         let frag_shader_stage_info = ash::vk::PipelineShaderStageCreateInfo::default()
             .stage(ash::vk::ShaderStageFlags::FRAGMENT)
-            .module(shader_module)
+            .module(*shader_module)
             .name(c"main_fs");
 
         let shader_stages = [vert_shader_stage_info, frag_shader_stage_info];
 
-        let binding_descriptions = &[VertexData::get_binding_description()];
-        let attribute_descriptions = VertexData::get_attribute_descriptions();
+        let binding_descriptions = &[
+            VertexData::get_binding_description(),
+            InstanceData::get_binding_description(),
+        ];
+        let attribute_descriptions = [
+            VertexData::get_attribute_descriptions(),
+            InstanceData::get_attribute_descriptions(),
+        ]
+        .concat();
 
         let vertex_input_info = ash::vk::PipelineVertexInputStateCreateInfo::default()
             .vertex_binding_descriptions(binding_descriptions)
@@ -1018,10 +1707,27 @@ impl VulkanApp {
             .logic_op_enable(false)
             .attachments(&colour_blend_attachments);
 
+        let push_constants_size = size_of::<PushConstants>() as u32;
+        if push_constants_size > device_limits.max_push_constants_size {
+            return error(&format!(
+                "Push constants need {push_constants_size} bytes, but the device only supports {}",
+                device_limits.max_push_constants_size
+            ));
+        }
+        let push_constant_range = ash::vk::PushConstantRange::default()
+            .stage_flags(PUSH_CONSTANT_STAGES)
+            .offset(0)
+            .size(push_constants_size);
+
         let pipeline_layout_info = ash::vk::PipelineLayoutCreateInfo::default()
-            .set_layouts(slice::from_ref(descriptor_set_layout));
-        let pipeline_layout =
-            unsafe { device.create_pipeline_layout(&pipeline_layout_info, None) }?;
+            .set_layouts(descriptor_set_layouts)
+            .push_constant_ranges(slice::from_ref(&push_constant_range));
+        let pipeline_layout = unsafe {
+            Owned::new(
+                device,
+                device.create_pipeline_layout(&pipeline_layout_info, None)?,
+            )
+        };
 
         let pipeline_info = ash::vk::GraphicsPipelineCreateInfo::default()
             .stages(&shader_stages)
@@ -1032,7 +1738,7 @@ impl VulkanApp {
             .multisample_state(&multisampling)
             .color_blend_state(&colour_blending)
             .dynamic_state(&dynamic_state)
-            .layout(pipeline_layout)
+            .layout(*pipeline_layout)
             .render_pass(render_pass)
             .subpass(0);
 
@@ -1042,18 +1748,20 @@ impl VulkanApp {
         }
         .map_err(|(_, e)| e)?;
 
-        Ok((shader_module, pipeline_layout, pipeline[0]))
+        Ok((shader_module, pipeline_layout, unsafe {
+            Owned::new(device, pipeline[0])
+        }))
     }
-    fn create_framebuffers(
-        device: &ash::Device,
-        swap_chain_image_views: &[ash::vk::ImageView],
+    pub(crate) fn create_framebuffers(
+        device: &owned::Device,
+        swap_chain_image_views: &[Owned<ash::vk::ImageView>],
         render_pass: ash::vk::RenderPass,
         swap_chain_extent: ash::vk::Extent2D,
-    ) -> Result<Vec<ash::vk::Framebuffer>> {
+    ) -> Result<Vec<Owned<ash::vk::Framebuffer>>> {
         let mut swap_chain_framebuffers = Vec::with_capacity(swap_chain_image_views.len());
 
         for image_view in swap_chain_image_views {
-            let attachments = &[*image_view];
+            let attachments = &[**image_view];
             let framebuffer_info = ash::vk::FramebufferCreateInfo::default()
                 .render_pass(render_pass)
                 .attachments(attachments)
@@ -1062,18 +1770,18 @@ impl VulkanApp {
                 .layers(1);
 
             let fb = unsafe { device.create_framebuffer(&framebuffer_info, None) }?;
-            swap_chain_framebuffers.push(fb);
+            swap_chain_framebuffers.push(unsafe { Owned::new(device, fb) });
         }
 
         Ok(swap_chain_framebuffers)
     }
     fn create_command_pool(
         instance: &ash::Instance,
-        device: &ash::Device,
+        device: &owned::Device,
         surface_instance: &ash::khr::surface::Instance,
         physical_device: ash::vk::PhysicalDevice,
         surface: ash::vk::SurfaceKHR,
-    ) -> Result<ash::vk::CommandPool> {
+    ) -> Result<Owned<ash::vk::CommandPool>> {
         let queue_family_indices = unsafe {
             Self::find_queue_families(instance, surface_instance, physical_device, surface)
         };
@@ -1083,23 +1791,23 @@ impl VulkanApp {
             .queue_family_index(queue_family_indices.graphics_family.unwrap());
 
         let command_pool = unsafe { device.create_command_pool(&pool_info, None)? };
-        Ok(command_pool)
+        Ok(unsafe { Owned::new(device, command_pool) })
     }
-    fn create_buffer(
+    pub(crate) fn create_buffer(
         instance: &ash::Instance,
-        device: &ash::Device,
+        device: &owned::Device,
         size: ash::vk::DeviceSize,
         physical_device: ash::vk::PhysicalDevice,
         usage: ash::vk::BufferUsageFlags,
         properties: ash::vk::MemoryPropertyFlags,
-    ) -> Result<(ash::vk::Buffer, ash::vk::DeviceMemory)> {
+    ) -> Result<(Owned<ash::vk::Buffer>, Owned<ash::vk::DeviceMemory>)> {
         let buffer_info = ash::vk::BufferCreateInfo::default()
             .size(size)
             .usage(usage)
             .sharing_mode(ash::vk::SharingMode::EXCLUSIVE);
 
-        let buffer = unsafe { device.create_buffer(&buffer_info, None) }?;
-        let memory_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let buffer = unsafe { Owned::new(device, device.create_buffer(&buffer_info, None)?) };
+        let memory_requirements = unsafe { device.get_buffer_memory_requirements(*buffer) };
         let memory_type_index = Self::find_memory_type(
             instance,
             physical_device,
@@ -1110,9 +1818,10 @@ impl VulkanApp {
             .allocation_size(memory_requirements.size)
             .memory_type_index(memory_type_index);
 
-        let buffer_memory = unsafe { device.allocate_memory(&alloc_info, None) }?;
+        let buffer_memory =
+            unsafe { Owned::new(device, device.allocate_memory(&alloc_info, None)?) };
 
-        unsafe { device.bind_buffer_memory(buffer, buffer_memory, 0) }?;
+        unsafe { device.bind_buffer_memory(*buffer, *buffer_memory, 0) }?;
 
         Ok((buffer, buffer_memory))
     }
@@ -1185,66 +1894,17 @@ impl VulkanApp {
     }
     fn transition_image_layout(
         device: &ash::Device,
+        synchronization2_device: Option<&ash::khr::synchronization2::Device>,
         command_pool: ash::vk::CommandPool,
         graphics_queue: ash::vk::Queue,
-        image: ash::vk::Image,
-        old_layout: ash::vk::ImageLayout,
-        new_layout: ash::vk::ImageLayout,
+        transition: &ImageTransition,
     ) -> Result<()> {
         let command_buffer = Self::begin_single_time_commands(device, command_pool)?;
 
-        let barrier = ash::vk::ImageMemoryBarrier::default()
-            .old_layout(old_layout)
-            .new_layout(new_layout)
-            .src_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
-            .dst_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
-            .image(image)
-            .subresource_range(
-                ash::vk::ImageSubresourceRange::default()
-                    .aspect_mask(ash::vk::ImageAspectFlags::COLOR)
-                    .base_mip_level(0)
-                    .level_count(1)
-                    .base_array_layer(0)
-                    .layer_count(1),
-            )
-            .src_access_mask(ash::vk::AccessFlags::empty()) // TODO
-            .dst_access_mask(ash::vk::AccessFlags::empty()) // TODO 
-            ;
-
-        let (barrier, source_stage, destination_stage) = if old_layout
-            == ash::vk::ImageLayout::UNDEFINED
-            && new_layout == ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL
-        {
-            (
-                barrier
-                    .src_access_mask(ash::vk::AccessFlags::empty())
-                    .dst_access_mask(ash::vk::AccessFlags::TRANSFER_WRITE),
-                ash::vk::PipelineStageFlags::TOP_OF_PIPE,
-                ash::vk::PipelineStageFlags::TRANSFER,
-            )
-        } else if old_layout == ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL
-            && new_layout == ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
-        {
-            (
-                barrier,
-                ash::vk::PipelineStageFlags::TRANSFER,
-                ash::vk::PipelineStageFlags::FRAGMENT_SHADER,
-            )
-        } else {
-            return error("Unsupported layout transition");
-        };
-
-        unsafe {
-            device.cmd_pipeline_barrier(
-                command_buffer,
-                source_stage,
-                destination_stage,
-                ash::vk::DependencyFlags::default(),
-                &[],
-                &[],
-                slice::from_ref(&barrier),
-            )
-        };
+        match synchronization2_device {
+            Some(sync2) => cmd_transition_image_layout2(sync2, command_buffer, transition)?,
+            None => cmd_transition_image_layout(device, command_buffer, transition)?,
+        }
 
         Self::end_single_time_commands(device, command_pool, graphics_queue, command_buffer)?;
         Ok(())
@@ -1294,11 +1954,11 @@ impl VulkanApp {
     }
     fn create_vertex_buffer(
         instance: &ash::Instance,
-        device: &ash::Device,
+        device: &owned::Device,
         command_pool: ash::vk::CommandPool,
         graphics_queue: ash::vk::Queue,
         physical_device: ash::vk::PhysicalDevice,
-    ) -> Result<(ash::vk::Buffer, ash::vk::DeviceMemory)> {
+    ) -> Result<(Owned<ash::vk::Buffer>, Owned<ash::vk::DeviceMemory>)> {
         let buffer_size = size_of_val(&VERTICES) as ash::vk::DeviceSize;
 
         let (staging_buffer, staging_buffer_memory) = Self::create_buffer(
@@ -1313,7 +1973,7 @@ impl VulkanApp {
 
         unsafe {
             let data = device.map_memory(
-                staging_buffer_memory,
+                *staging_buffer_memory,
                 0,
                 buffer_size,
                 ash::vk::MemoryMapFlags::empty(),
@@ -1322,7 +1982,7 @@ impl VulkanApp {
             //  - data will have alignment of `VkPhysicalDeviceLimits::minMemoryMapAlignment` (= 4096 on my system)
             //  - VERTICES has alignment 4, which is a factor of 4096, thus will be fine
             core::ptr::write_unaligned(data as _, VERTICES);
-            device.unmap_memory(staging_buffer_memory);
+            device.unmap_memory(*staging_buffer_memory);
         };
 
         let (vertex_buffer, vertex_buffer_memory) = Self::create_buffer(
@@ -1338,25 +1998,20 @@ impl VulkanApp {
             device,
             command_pool,
             graphics_queue,
-            staging_buffer,
-            vertex_buffer,
+            *staging_buffer,
+            *vertex_buffer,
             buffer_size,
         )?;
 
-        unsafe {
-            device.destroy_buffer(staging_buffer, None);
-            device.free_memory(staging_buffer_memory, None);
-        }
-
         Ok((vertex_buffer, vertex_buffer_memory))
     }
     fn create_index_buffer(
         instance: &ash::Instance,
-        device: &ash::Device,
+        device: &owned::Device,
         command_pool: ash::vk::CommandPool,
         graphics_queue: ash::vk::Queue,
         physical_device: ash::vk::PhysicalDevice,
-    ) -> Result<(ash::vk::Buffer, ash::vk::DeviceMemory)> {
+    ) -> Result<(Owned<ash::vk::Buffer>, Owned<ash::vk::DeviceMemory>)> {
         let buffer_size = size_of_val(&INDICES) as ash::vk::DeviceSize;
 
         let (staging_buffer, staging_buffer_memory) = Self::create_buffer(
@@ -1371,13 +2026,13 @@ impl VulkanApp {
 
         unsafe {
             let data = device.map_memory(
-                staging_buffer_memory,
+                *staging_buffer_memory,
                 0,
                 buffer_size,
                 ash::vk::MemoryMapFlags::empty(),
             )?;
             core::ptr::write_unaligned(data as _, INDICES);
-            device.unmap_memory(staging_buffer_memory);
+            device.unmap_memory(*staging_buffer_memory);
         };
 
         let (index_buffer, index_buffer_memory) = Self::create_buffer(
@@ -1393,36 +2048,26 @@ impl VulkanApp {
             device,
             command_pool,
             graphics_queue,
-            staging_buffer,
-            index_buffer,
+            *staging_buffer,
+            *index_buffer,
             buffer_size,
         )?;
 
-        unsafe {
-            device.destroy_buffer(staging_buffer, None);
-            device.free_memory(staging_buffer_memory, None);
-        }
-
         Ok((index_buffer, index_buffer_memory))
     }
     fn create_uniform_buffers(
         instance: &ash::Instance,
-        device: &ash::Device,
+        device: &owned::Device,
         physical_device: ash::vk::PhysicalDevice,
-    ) -> Result<(
-        Vec<ash::vk::Buffer>,
-        Vec<ash::vk::DeviceMemory>,
-        Vec<*mut ffi::c_void>,
-    )> {
-        let buffer_size = size_of::<UniformBufferObject>();
-
-        let mut uniform_buffers = Vec::with_capacity(buffer_size);
-        let mut uniform_buffers_memory = Vec::with_capacity(buffer_size);
-        let mut uniform_buffers_mapped = Vec::with_capacity(buffer_size);
+        frames_in_flight: u32,
+    ) -> Result<MappedBuffers> {
+        let buffer_size = size_of::<UniformBufferObject>() as ash::vk::DeviceSize;
 
-        let buffer_size = buffer_size as ash::vk::DeviceSize;
+        let mut uniform_buffers = Vec::with_capacity(frames_in_flight as usize);
+        let mut uniform_buffers_memory = Vec::with_capacity(frames_in_flight as usize);
+        let mut uniform_buffers_mapped = Vec::with_capacity(frames_in_flight as usize);
 
-        for _ in 0..buffer_size {
+        for _ in 0..frames_in_flight {
             let (buffer, memory) = Self::create_buffer(
                 instance,
                 device,
@@ -1433,12 +2078,11 @@ impl VulkanApp {
                     | ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
             )?;
 
-            uniform_buffers.push(buffer);
-            uniform_buffers_memory.push(memory);
-
             let map = unsafe {
-                device.map_memory(memory, 0, buffer_size, ash::vk::MemoryMapFlags::empty())
+                device.map_memory(*memory, 0, buffer_size, ash::vk::MemoryMapFlags::empty())
             }?;
+            uniform_buffers.push(buffer);
+            uniform_buffers_memory.push(memory);
             uniform_buffers_mapped.push(map);
         }
 
@@ -1448,112 +2092,104 @@ impl VulkanApp {
             uniform_buffers_mapped,
         ))
     }
-    fn create_descriptor_pool(device: &ash::Device) -> Result<ash::vk::DescriptorPool> {
-        let pool_sizes = [
-            ash::vk::DescriptorPoolSize::default()
-                .ty(ash::vk::DescriptorType::UNIFORM_BUFFER)
-                .descriptor_count(MAX_FRAMES_IN_FLIGHT),
-            ash::vk::DescriptorPoolSize::default()
-                .ty(ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                .descriptor_count(MAX_FRAMES_IN_FLIGHT),
-        ];
-        let pool_info = ash::vk::DescriptorPoolCreateInfo::default()
-            .pool_sizes(&pool_sizes)
-            .max_sets(MAX_FRAMES_IN_FLIGHT);
+    fn create_object_buffers(
+        instance: &ash::Instance,
+        device: &owned::Device,
+        physical_device: ash::vk::PhysicalDevice,
+        frames_in_flight: u32,
+    ) -> Result<MappedBuffers> {
+        let buffer_size = (size_of::<ObjectData>() * MAX_OBJECTS as usize) as ash::vk::DeviceSize;
 
-        let descriptor_pool = unsafe { device.create_descriptor_pool(&pool_info, None) }?;
+        let mut object_buffers = Vec::with_capacity(frames_in_flight as usize);
+        let mut object_buffers_memory = Vec::with_capacity(frames_in_flight as usize);
+        let mut object_buffers_mapped = Vec::with_capacity(frames_in_flight as usize);
 
-        Ok(descriptor_pool)
+        for _ in 0..frames_in_flight {
+            let (buffer, memory) = Self::create_buffer(
+                instance,
+                device,
+                buffer_size,
+                physical_device,
+                ash::vk::BufferUsageFlags::STORAGE_BUFFER,
+                ash::vk::MemoryPropertyFlags::HOST_COHERENT
+                    | ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )?;
+
+            let map = unsafe {
+                device.map_memory(*memory, 0, buffer_size, ash::vk::MemoryMapFlags::empty())
+            }?;
+            object_buffers.push(buffer);
+            object_buffers_memory.push(memory);
+            object_buffers_mapped.push(map);
+        }
+
+        Ok((object_buffers, object_buffers_memory, object_buffers_mapped))
     }
-    fn create_descriptor_sets(
-        device: &ash::Device,
-        descriptor_set_layout: ash::vk::DescriptorSetLayout,
-        descriptor_pool: ash::vk::DescriptorPool,
-        uniform_buffers: &[ash::vk::Buffer],
-        texture_image_view: ash::vk::ImageView,
-        texture_sampler: ash::vk::Sampler,
-    ) -> Result<Vec<ash::vk::DescriptorSet>> {
-        let layouts = [descriptor_set_layout; MAX_FRAMES_IN_FLIGHT as usize];
-        let alloc_info = ash::vk::DescriptorSetAllocateInfo::default()
-            .descriptor_pool(descriptor_pool)
-            .set_layouts(&layouts);
-
-        let descriptor_sets = unsafe { device.allocate_descriptor_sets(&alloc_info) }?;
-
-        for (&buffer, &descriptor_set) in uniform_buffers.iter().zip(&descriptor_sets) {
-            let buffer_info = ash::vk::DescriptorBufferInfo::default()
-                .buffer(buffer)
-                .offset(0)
-                .range(size_of::<UniformBufferObject>() as _);
-
-            let image_info = ash::vk::DescriptorImageInfo::default()
-                .image_layout(ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .image_view(texture_image_view)
-                .sampler(texture_sampler);
-
-            let descriptor_writes = [
-                ash::vk::WriteDescriptorSet::default()
-                    .dst_set(descriptor_set)
-                    .dst_binding(0)
-                    .dst_array_element(0)
-                    .descriptor_type(ash::vk::DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(slice::from_ref(&buffer_info)),
-                ash::vk::WriteDescriptorSet::default()
-                    .dst_set(descriptor_set)
-                    .dst_binding(1)
-                    .dst_array_element(0)
-                    .descriptor_type(ash::vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .descriptor_count(1)
-                    .image_info(slice::from_ref(&image_info)),
-            ];
-            unsafe { device.update_descriptor_sets(&descriptor_writes, &[]) }
+    fn create_instance_buffers(
+        instance: &ash::Instance,
+        device: &owned::Device,
+        physical_device: ash::vk::PhysicalDevice,
+        frames_in_flight: u32,
+    ) -> Result<MappedBuffers> {
+        let buffer_size =
+            (size_of::<InstanceData>() * MAX_INSTANCES as usize) as ash::vk::DeviceSize;
+
+        let mut instance_buffers = Vec::with_capacity(frames_in_flight as usize);
+        let mut instance_buffers_memory = Vec::with_capacity(frames_in_flight as usize);
+        let mut instance_buffers_mapped = Vec::with_capacity(frames_in_flight as usize);
+
+        for _ in 0..frames_in_flight {
+            let (buffer, memory) = Self::create_buffer(
+                instance,
+                device,
+                buffer_size,
+                physical_device,
+                ash::vk::BufferUsageFlags::VERTEX_BUFFER,
+                ash::vk::MemoryPropertyFlags::HOST_COHERENT
+                    | ash::vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )?;
+
+            let map = unsafe {
+                device.map_memory(*memory, 0, buffer_size, ash::vk::MemoryMapFlags::empty())
+            }?;
+            instance_buffers.push(buffer);
+            instance_buffers_memory.push(memory);
+            instance_buffers_mapped.push(map);
         }
 
-        Ok(descriptor_sets)
+        Ok((
+            instance_buffers,
+            instance_buffers_memory,
+            instance_buffers_mapped,
+        ))
     }
     fn create_command_buffers(
         device: &ash::Device,
         command_pool: ash::vk::CommandPool,
+        frames_in_flight: u32,
     ) -> Result<Vec<ash::vk::CommandBuffer>> {
         let alloc_info = ash::vk::CommandBufferAllocateInfo::default()
             .command_pool(command_pool)
             .level(ash::vk::CommandBufferLevel::PRIMARY)
-            .command_buffer_count(MAX_FRAMES_IN_FLIGHT);
+            .command_buffer_count(frames_in_flight);
 
         let command_buffers = unsafe { device.allocate_command_buffers(&alloc_info) }?;
         Ok(command_buffers)
     }
-    fn create_sync_objects(
-        device: &ash::Device,
-    ) -> Result<(
-        Vec<ash::vk::Semaphore>,
-        Vec<ash::vk::Semaphore>,
-        Vec<ash::vk::Fence>,
-    )> {
+    fn create_semaphores(
+        device: &owned::Device,
+        count: u32,
+    ) -> Result<Vec<Owned<ash::vk::Semaphore>>> {
         let semaphore_info = ash::vk::SemaphoreCreateInfo::default();
-        let fence_info =
-            ash::vk::FenceCreateInfo::default().flags(ash::vk::FenceCreateFlags::SIGNALED);
-
-        let mut image_available_semaphores = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
-        let mut render_finished_semaphores = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
-        let mut in_flight_fences = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
-
-        for _ in 0..MAX_FRAMES_IN_FLIGHT {
-            image_available_semaphores
-                .push(unsafe { device.create_semaphore(&semaphore_info, None) }?);
-            render_finished_semaphores
-                .push(unsafe { device.create_semaphore(&semaphore_info, None) }?);
-            in_flight_fences.push(unsafe { device.create_fence(&fence_info, None) }?);
-        }
-
-        Ok((
-            image_available_semaphores,
-            render_finished_semaphores,
-            in_flight_fences,
-        ))
+        (0..count)
+            .map(|_| {
+                let semaphore = unsafe { device.create_semaphore(&semaphore_info, None) }?;
+                Ok(unsafe { Owned::new(device, semaphore) })
+            })
+            .collect()
     }
     fn record_command_buffer(
-        &self,
+        &mut self,
         command_buffer: ash::vk::CommandBuffer,
         image_index: u32,
     ) -> Result<()> {
@@ -1564,6 +2200,38 @@ impl VulkanApp {
                 .begin_command_buffer(command_buffer, &begin_info)
         }?;
 
+        if let Some(gpu_timer) = &mut self.vulkan.gpu_timer {
+            gpu_timer.cmd_begin_frame(
+                &self.vulkan.device,
+                command_buffer,
+                self.current_frame as usize,
+            );
+        }
+        self.vulkan.gpu_queries.cmd_begin_frame(
+            &self.vulkan.device,
+            command_buffer,
+            self.current_frame as usize,
+        );
+        self.cmd_begin_gpu_region(command_buffer, "frame");
+
+        let object_count = self.scene.objects().len() as u32;
+        if self.gpu_driven {
+            self.cmd_begin_gpu_region(command_buffer, "cull");
+            let (view, projection) = self.camera_matrices();
+            let cull_push_constants = CullPushConstants {
+                frustum_planes: gpu_culling::frustum_planes(projection * view),
+                object_count,
+                _padding: [0; 3],
+            };
+            self.vulkan.gpu_culling.cmd_cull(
+                &self.vulkan.device,
+                command_buffer,
+                self.current_frame as usize,
+                &cull_push_constants,
+            );
+            self.cmd_end_gpu_region(command_buffer);
+        }
+
         let clear_values = [ash::vk::ClearValue {
             color: ash::vk::ClearColorValue {
                 float32: [0.0, 0.0, 0.0, 1.0],
@@ -1571,14 +2239,15 @@ impl VulkanApp {
         }];
 
         let render_pass_info = ash::vk::RenderPassBeginInfo::default()
-            .render_pass(self.vulkan.render_pass)
-            .framebuffer(self.vulkan.swap_chain_framebuffers[image_index as usize])
+            .render_pass(*self.vulkan.render_pass)
+            .framebuffer(*self.vulkan.output_pass.targets().scene_framebuffer)
             .render_area(ash::vk::Rect2D {
                 offset: ash::vk::Offset2D { x: 0, y: 0 },
                 extent: self.vulkan.swapchain_extent,
             })
             .clear_values(&clear_values);
 
+        self.cmd_begin_gpu_region(command_buffer, "scene");
         unsafe {
             self.vulkan.device.cmd_begin_render_pass(
                 command_buffer,
@@ -1592,11 +2261,14 @@ impl VulkanApp {
             self.vulkan.device.cmd_bind_pipeline(
                 command_buffer,
                 ash::vk::PipelineBindPoint::GRAPHICS,
-                self.vulkan.graphics_pipeline,
+                *self.vulkan.graphics_pipeline,
             );
 
-            let vertex_buffers = [self.vulkan.vertex_buffer];
-            let offsets = [0 as ash::vk::DeviceSize];
+            let vertex_buffers = [
+                *self.vulkan.vertex_buffer,
+                *self.vulkan.instance_buffers[self.current_frame as usize],
+            ];
+            let offsets = [0 as ash::vk::DeviceSize, 0];
             self.vulkan.device.cmd_bind_vertex_buffers(
                 command_buffer,
                 0,
@@ -1605,7 +2277,7 @@ impl VulkanApp {
             );
             self.vulkan.device.cmd_bind_index_buffer(
                 command_buffer,
-                self.vulkan.index_buffer,
+                *self.vulkan.index_buffer,
                 0,
                 ash::vk::IndexType::UINT16,
             );
@@ -1631,60 +2303,186 @@ impl VulkanApp {
             self.vulkan.device.cmd_bind_descriptor_sets(
                 command_buffer,
                 ash::vk::PipelineBindPoint::GRAPHICS,
-                self.vulkan.pipeline_layout,
+                *self.vulkan.pipeline_layout,
                 0,
-                slice::from_ref(&self.vulkan.descriptor_sets[self.current_frame as usize]),
+                &[
+                    self.vulkan.descriptor_sets[self.current_frame as usize],
+                    self.vulkan.bindless.descriptor_set,
+                ],
                 &[],
             );
 
-            self.vulkan
-                .device
-                .cmd_draw_indexed(command_buffer, INDICES.len() as u32, 1, 0, 0, 0);
+            if self.gpu_driven {
+                let push_constants = PushConstants {
+                    object_index: INDIRECT_OBJECT_INDEX,
+                };
+                self.cmd_push_constants(command_buffer, 0, &push_constants)?;
+
+                self.vulkan
+                    .debug_utils
+                    .cmd_insert_label(command_buffer, "indirect draws");
+                self.vulkan.gpu_culling.cmd_draw(
+                    &self.vulkan.device,
+                    command_buffer,
+                    self.current_frame as usize,
+                    object_count,
+                );
+            }
+
+            let direct_objects = if self.gpu_driven {
+                &[][..]
+            } else {
+                self.vulkan
+                    .debug_utils
+                    .cmd_insert_label(command_buffer, "direct draws");
+                self.scene.objects()
+            };
+            for (object_index, (object, instances)) in
+                direct_objects.iter().zip(&self.instance_ranges).enumerate()
+            {
+                let mesh = self.vulkan.meshes[object.mesh.0];
+                let push_constants = PushConstants {
+                    object_index: object_index as u32,
+                };
+                self.cmd_push_constants(command_buffer, 0, &push_constants)?;
+
+                self.vulkan.gpu_queries.cmd_begin_occlusion(
+                    &self.vulkan.device,
+                    command_buffer,
+                    self.current_frame as usize,
+                    object_index as u32,
+                );
+                self.vulkan.device.cmd_draw_indexed(
+                    command_buffer,
+                    mesh.index_count,
+                    instances.len() as u32,
+                    mesh.first_index,
+                    mesh.vertex_offset,
+                    instances.start,
+                );
+                self.vulkan.gpu_queries.cmd_end_occlusion(
+                    &self.vulkan.device,
+                    command_buffer,
+                    self.current_frame as usize,
+                    object_index as u32,
+                );
+            }
 
             self.vulkan.device.cmd_end_render_pass(command_buffer);
         }
+        self.cmd_end_gpu_region(command_buffer);
+
+        self.cmd_begin_gpu_region(command_buffer, "output");
+        self.vulkan.output_pass.cmd_draw(
+            &self.vulkan.device,
+            command_buffer,
+            image_index,
+            self.vulkan.swapchain_extent,
+            self.vulkan.swapchain_surface_format,
+        );
+        self.cmd_end_gpu_region(command_buffer);
+
+        // End "frame"
+        self.cmd_end_gpu_region(command_buffer);
+        self.vulkan.gpu_queries.cmd_end_frame(
+            &self.vulkan.device,
+            command_buffer,
+            self.current_frame as usize,
+        );
 
         unsafe { self.vulkan.device.end_command_buffer(command_buffer) }?;
 
         Ok(())
     }
+    /// Start a labelled region of the current frame, timed on the GPU if timestamps are supported
+    fn cmd_begin_gpu_region(&mut self, command_buffer: ash::vk::CommandBuffer, name: &'static str) {
+        self.vulkan
+            .debug_utils
+            .cmd_begin_label(command_buffer, name);
+        if let Some(gpu_timer) = &mut self.vulkan.gpu_timer {
+            gpu_timer.cmd_begin(
+                &self.vulkan.device,
+                command_buffer,
+                self.current_frame as usize,
+                name,
+            );
+        }
+    }
+    fn cmd_end_gpu_region(&mut self, command_buffer: ash::vk::CommandBuffer) {
+        if let Some(gpu_timer) = &mut self.vulkan.gpu_timer {
+            gpu_timer.cmd_end(
+                &self.vulkan.device,
+                command_buffer,
+                self.current_frame as usize,
+            );
+        }
+        self.vulkan.debug_utils.cmd_end_label(command_buffer);
+    }
     fn draw_frame(&mut self) -> Result<()> {
-        let current_frame = self.current_frame as usize;
-
-        unsafe {
-            self.vulkan.device.wait_for_fences(
-                &[self.vulkan.in_flight_fences[current_frame]],
-                true,
-                u64::MAX,
-            )?;
+        let current_frame = self.vulkan.frame_sync.slot();
+        self.current_frame = current_frame as u32;
+
+        self.frame_stats.begin_phases();
+        self.vulkan.frame_sync.wait_for_slot(&self.vulkan.device)?;
+        self.frame_stats.record_latency();
+        self.vulkan.frame_descriptors[current_frame].reset()?;
+        self.vulkan.deletion_queue.collect(current_frame);
+        if let Some(gpu_timer) = &mut self.vulkan.gpu_timer {
+            // Safety: waited for the submission these queries were recorded in
+            let gpu_times = unsafe { gpu_timer.read_results(&self.vulkan.device, current_frame) }?;
+            self.frame_stats.record_gpu_times(gpu_times);
         }
+        // Safety: as above
+        unsafe {
+            self.vulkan
+                .gpu_queries
+                .read_results(&self.vulkan.device, current_frame)
+        }?;
+        self.frame_stats.end_phase(FramePhase::WaitFence);
 
-        let acquire_image_result = unsafe {
-            self.vulkan.swapchain_device.acquire_next_image(
-                self.vulkan.swapchain,
-                u64::MAX,
-                self.vulkan.image_available_semaphores[current_frame],
-                ash::vk::Fence::null(),
-            )
-        };
+        // Recreate before acquiring, so this frame is already drawn at the new size. The old
+        //  swapchain is retired rather than waited on, so a live resize keeps presenting
+        if self.framebuffer_resized.swap(false, Ordering::Relaxed) {
+            self.recreate_swap_chain()?;
+        }
 
-        let image_index = match acquire_image_result {
-            Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                self.recreate_swap_chain()?;
-                return Ok(());
+        let image_index = loop {
+            let acquire_image_result = unsafe {
+                self.vulkan.swapchain_device.acquire_next_image(
+                    *self.vulkan.swapchain,
+                    u64::MAX,
+                    *self.vulkan.image_available_semaphores[current_frame],
+                    ash::vk::Fence::null(),
+                )
+            };
+            match acquire_image_result {
+                // Nothing was acquired or signalled, so try again with a new swapchain
+                Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => self.recreate_swap_chain()?,
+                // A suboptimal image can still be presented, and is recreated after that
+                Ok((image_index, _)) => break image_index,
+                Err(e) => return Err(e.into()),
             }
-            Ok((image_index, _)) => image_index,
-            Err(e) => return Err(e.into()),
         };
 
-        self.update_uniform_buffer(image_index);
-
-        unsafe {
-            // Only reset fences if we are submitting work
+        // Only this frame's slot has been waited on, and the image may last have been rendered
+        //  to by another frame still in flight
+        let frame = self.vulkan.frame_sync.frame();
+        if let Some(image_frame) = self.vulkan.images_in_flight[image_index as usize].replace(frame)
+        {
             self.vulkan
-                .device
-                .reset_fences(&[self.vulkan.in_flight_fences[current_frame]])?;
+                .frame_sync
+                .wait_for_frame(&self.vulkan.device, image_frame)?;
+        }
+        self.frame_stats.end_phase(FramePhase::AcquireImage);
+
+        self.update_uniform_buffer(self.current_frame);
+        self.animate_scene();
+        self.update_object_buffer(self.current_frame)?;
+        self.update_instance_buffer(self.current_frame)?;
+        if self.gpu_driven {
+            self.update_cull_objects(self.current_frame)?;
         }
+        self.write_scene_descriptor_set(current_frame)?;
 
         unsafe {
             self.vulkan.device.reset_command_buffer(
@@ -1693,27 +2491,27 @@ impl VulkanApp {
             )?;
             self.record_command_buffer(self.vulkan.command_buffers[current_frame], image_index)?;
         }
+        self.frame_stats.end_phase(FramePhase::Record);
 
-        let wait_semaphores = [self.vulkan.image_available_semaphores[current_frame]];
-        let wait_stages = [ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let command_buffers = [self.vulkan.command_buffers[current_frame]];
-        let signal_semaphores = [self.vulkan.render_finished_semaphores[current_frame]];
-        let submit_info = ash::vk::SubmitInfo::default()
-            .wait_semaphores(&wait_semaphores)
-            .wait_dst_stage_mask(&wait_stages)
-            .command_buffers(&command_buffers)
-            .signal_semaphores(&signal_semaphores);
-        let submit_info = [submit_info];
-
+        let signal_semaphores = [*self.vulkan.render_finished_semaphores[image_index as usize]];
+        let submit = Submit::default()
+            .wait_binary(
+                *self.vulkan.image_available_semaphores[current_frame],
+                ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            )
+            .signal_binary(signal_semaphores[0]);
         unsafe {
-            self.vulkan.device.queue_submit(
+            self.vulkan.frame_sync.submit_frame(
+                &self.vulkan.device,
                 self.vulkan.graphics_queue,
-                &submit_info,
-                self.vulkan.in_flight_fences[current_frame],
+                submit,
+                slice::from_ref(&self.vulkan.command_buffers[current_frame]),
             )
         }?;
+        self.vulkan.deletion_queue.submitted(current_frame);
+        self.frame_stats.end_phase(FramePhase::Submit);
 
-        let swapchains = [self.vulkan.swapchain];
+        let swapchains = [*self.vulkan.swapchain];
         let image_indices = [image_index];
         let present_info = ash::vk::PresentInfoKHR::default()
             .wait_semaphores(&signal_semaphores)
@@ -1725,51 +2523,297 @@ impl VulkanApp {
                 .swapchain_device
                 .queue_present(self.vulkan.present_queue, &present_info)
         };
-        let resized = self.framebuffer_resized.load(Ordering::Relaxed);
-        match (present_result, resized) {
-            (Ok(true), _) | (_, true) => {
-                // Suboptimal or resized
-                self.framebuffer_resized.store(false, Ordering::Relaxed);
-                self.recreate_swap_chain()?;
+        self.frame_stats.end_phase(FramePhase::Present);
+        match present_result {
+            Ok(true) | Err(ash::vk::Result::ERROR_OUT_OF_DATE_KHR) => self.recreate_swap_chain()?,
+            Err(e) => return Err(e.into()),
+            Ok(false) => { /* All good */ }
+        }
+
+        Ok(())
+    }
+    /// Push `constants` into the push constant range of the graphics pipeline layout at `offset`.
+    ///
+    /// Fails without recording anything if the constants aren't 4 byte aligned, or don't fit in
+    /// the `PushConstants` range declared by the pipeline layout.
+    fn cmd_push_constants<T: Pod>(
+        &self,
+        command_buffer: ash::vk::CommandBuffer,
+        offset: u32,
+        constants: &T,
+    ) -> Result<()> {
+        let bytes = pod::bytes_of(constants);
+        let size = bytes.len();
+        if offset % 4 != 0 || size % 4 != 0 {
+            return error(&format!(
+                "Push constants at offset {offset} with size {size} aren't a multiple of 4 bytes"
+            ));
+        }
+        let range_size = size_of::<PushConstants>();
+        if offset as usize + size > range_size {
+            return error(&format!(
+                "Push constants at offset {offset} with size {size} exceed the {range_size} byte push constant range"
+            ));
+        }
+
+        unsafe {
+            self.vulkan.device.cmd_push_constants(
+                command_buffer,
+                *self.vulkan.pipeline_layout,
+                PUSH_CONSTANT_STAGES,
+                offset,
+                bytes,
+            )
+        };
+
+        Ok(())
+    }
+    /// The camera's view and projection matrices for the current swapchain extent
+    fn camera_matrices(&self) -> (glam::Mat4, glam::Mat4) {
+        let aspect_ratio =
+            self.vulkan.swapchain_extent.width as f32 / self.vulkan.swapchain_extent.height as f32;
+        (self.camera.view(), self.camera.projection(aspect_ratio))
+    }
+    fn update_uniform_buffer(&mut self, current_frame: u32) {
+        let (view, projection) = self.camera_matrices();
+        let ubo = UniformBufferObject { view, projection };
+
+        let map = self.vulkan.uniform_buffers_mapped[current_frame as usize];
+        unsafe { ptr::write_unaligned(map as _, ubo) };
+    }
+    fn animate_scene(&mut self) {
+        let time = START_TIME.elapsed().as_secs_f32();
+
+        for (i, object) in self.scene.objects_mut().iter_mut().enumerate() {
+            if object.instances.is_empty() {
+                let angle = time * PI / 2.0 + i as f32 * PI / 3.0;
+                object.transform.rotation = glam::Quat::from_rotation_z(angle);
+            } else {
+                for (index, instance) in object.instances.iter_mut().enumerate() {
+                    *instance = Self::grid_instance(index as u32, time);
+                }
             }
-            (Err(e), _) => return Err(e.into()),
-            (Ok(false), false) => { /* All good */ }
         }
+    }
+    /// Pack the instances of every object in the scene into the current frame's instance buffer,
+    /// recording where each object's instances ended up in `instance_ranges`
+    fn update_instance_buffer(&mut self, current_frame: u32) -> Result<()> {
+        let map = self.vulkan.instance_buffers_mapped[current_frame as usize] as *mut InstanceData;
+
+        // Every non-instanced object shares the default instance at the start of the buffer
+        unsafe { ptr::write_unaligned(map, DEFAULT_INSTANCE) };
+        let mut next_instance = 1;
+
+        self.instance_ranges.clear();
+        for object in self.scene.objects() {
+            if object.instances.is_empty() {
+                self.instance_ranges.push(0..1);
+                continue;
+            }
 
-        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+            let count = object.instances.len() as u32;
+            if next_instance + count > MAX_INSTANCES {
+                return error(&format!(
+                    "Scene has more than {MAX_INSTANCES} instances, which is the most supported"
+                ));
+            }
+
+            // Safety: the buffer holds `MAX_INSTANCES` entries, and we've checked this fits
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    object.instances.as_ptr(),
+                    map.add(next_instance as usize),
+                    object.instances.len(),
+                )
+            };
+            self.instance_ranges
+                .push(next_instance..next_instance + count);
+            next_instance += count;
+        }
 
         Ok(())
     }
-    fn update_uniform_buffer(&mut self, current_image: u32) {
-        static START_TIME: LazyLock<Instant> = LazyLock::new(Instant::now);
-        LazyLock::force(&START_TIME);
+    /// Write the transforms of every object in the scene into the current frame's object buffer
+    fn update_object_buffer(&mut self, current_frame: u32) -> Result<()> {
+        let objects = self.scene.objects();
+        if objects.len() > MAX_OBJECTS as usize {
+            return error(&format!(
+                "Scene has {} objects, but at most {MAX_OBJECTS} are supported",
+                objects.len()
+            ));
+        }
 
-        let current_time = Instant::now();
-        let time = (current_time - *START_TIME).as_secs_f32();
+        let map = self.vulkan.object_buffers_mapped[current_frame as usize] as *mut ObjectData;
+        for (i, object) in objects.iter().enumerate() {
+            let Some(texture) = self.vulkan.materials.get(object.material.0 as usize) else {
+                return error(&format!(
+                    "Scene uses unknown material {}",
+                    object.material.0
+                ));
+            };
+            let object_data = ObjectData {
+                model: object.transform.matrix(),
+                texture_index: texture.image,
+                sampler_index: texture.sampler,
+                _padding: [0; 2],
+            };
+            // Safety: the buffer holds `MAX_OBJECTS` entries, and `i < MAX_OBJECTS`
+            unsafe { ptr::write_unaligned(map.add(i), object_data) };
+        }
 
-        let model = glam::Mat4::from_axis_angle(glam::Vec3::Z, time * PI / 2.0);
-        let view = glam::Mat4::look_at_rh(
-            glam::Vec3::splat(2.0),
-            glam::Vec3::splat(0.0),
-            glam::Vec3::Z,
-        );
-        let mut projection = glam::Mat4::perspective_rh(
-            PI / 4.0,
-            self.vulkan.swapchain_extent.width as f32 / self.vulkan.swapchain_extent.height as f32,
-            0.1,
-            10.0,
-        );
-        projection.y_axis.y *= -1.0;
+        Ok(())
+    }
+    /// Allocate and write this frame's scene descriptor set, for `record_command_buffer` to bind
+    fn write_scene_descriptor_set(&mut self, current_frame: usize) -> Result<()> {
+        let descriptor_set = self.vulkan.frame_descriptors[current_frame]
+            .allocate(*self.vulkan.descriptor_set_layout)?;
+        DescriptorWriter::default()
+            .buffer(
+                0,
+                ash::vk::DescriptorType::UNIFORM_BUFFER,
+                *self.vulkan.uniform_buffers[current_frame],
+                0,
+                size_of::<UniformBufferObject>() as _,
+            )
+            .buffer(
+                2,
+                ash::vk::DescriptorType::STORAGE_BUFFER,
+                *self.vulkan.object_buffers[current_frame],
+                0,
+                ash::vk::WHOLE_SIZE,
+            )
+            .buffer(
+                3,
+                ash::vk::DescriptorType::STORAGE_BUFFER,
+                *self.vulkan.gpu_culling.draw_object_index_buffers[current_frame],
+                0,
+                ash::vk::WHOLE_SIZE,
+            )
+            .update(&self.vulkan.device, descriptor_set);
+        self.vulkan.descriptor_sets[current_frame] = descriptor_set;
 
-        let ubo = UniformBufferObject {
-            model,
-            view,
-            projection,
-        };
+        Ok(())
+    }
+    /// Write a culling entry for every object in the scene into the current frame's cull buffer.
+    ///
+    /// Must be called after `update_instance_buffer`, as the draws use its `instance_ranges`.
+    fn update_cull_objects(&mut self, current_frame: u32) -> Result<()> {
+        let meshes = &self.vulkan.meshes;
+        let cull_objects = self
+            .scene
+            .objects()
+            .iter()
+            .zip(&self.instance_ranges)
+            .enumerate()
+            .map(|(object_index, (object, instances))| {
+                let mesh = &meshes[object.mesh.0];
+                CullObject {
+                    bounding_sphere: object.bounding_sphere(mesh),
+                    draw: DrawIndexedIndirectCommand {
+                        index_count: mesh.index_count,
+                        instance_count: instances.len() as u32,
+                        first_index: mesh.first_index,
+                        vertex_offset: mesh.vertex_offset,
+                        first_instance: instances.start,
+                    },
+                    object_index: object_index as u32,
+                }
+            });
 
-        let map = self.vulkan.uniform_buffers_mapped[current_image as usize];
-        unsafe { ptr::write_unaligned(map as _, ubo) };
+        self.vulkan
+            .gpu_culling
+            .update_objects(current_frame as usize, cull_objects)?;
+
+        Ok(())
+    }
+    /// Validation errors and warnings reported so far, if validation is enabled
+    pub fn validation_counts(&self) -> Option<ValidationCounts> {
+        self.vulkan
+            .instance
+            .debug_messenger()
+            .map(DebugMessenger::counts)
+    }
+    /// Pipeline statistics for the most recent frame the GPU has finished, if supported
+    pub fn pipeline_statistics(&self) -> Option<PipelineStatistics> {
+        self.vulkan.gpu_queries.statistics()
     }
+    /// Samples drawn for each scene object, from the most recent finished frame which drew
+    /// objects directly rather than with GPU culling
+    pub fn object_occlusion(&self) -> &[u64] {
+        self.vulkan.gpu_queries.occlusion()
+    }
+    fn log_gpu_queries(&self) {
+        match self.pipeline_statistics() {
+            Some(statistics) => {
+                log::info!(
+                    target: target::FRAME,
+                    "Input assembly: {} vertices, {} primitives",
+                    statistics.input_assembly_vertices, statistics.input_assembly_primitives
+                );
+                log::info!(
+                    target: target::FRAME,
+                    "Clipping: {} primitives in, {} out",
+                    statistics.clipping_invocations, statistics.clipping_primitives
+                );
+                log::info!(
+                    target: target::FRAME,
+                    "Invocations: {} vertex, {} fragment, {} compute",
+                    statistics.vertex_shader_invocations,
+                    statistics.fragment_shader_invocations,
+                    statistics.compute_shader_invocations
+                );
+            }
+            None => log::info!(target: target::FRAME, "Pipeline statistics aren't supported"),
+        }
+
+        let occlusion = self.object_occlusion();
+        if occlusion.is_empty() {
+            log::info!(
+                target: target::FRAME,
+                "No occlusion results yet, they need GPU culling to be turned off"
+            );
+            return;
+        }
+        let visible = occlusion.iter().filter(|&&samples| samples > 0).count();
+        log::info!(
+            target: target::FRAME,
+            "{visible} of {} objects visible, samples per object: {occlusion:?}",
+            occlusion.len()
+        );
+    }
+    /// Switch to `present_mode`, recreating the swapchain to apply it
+    fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<()> {
+        self.swapchain_settings.present_mode = present_mode;
+        self.recreate_swap_chain()?;
+        log::info!(
+            target: target::SWAPCHAIN,
+            "Requested present mode {present_mode:?}, using {:?}",
+            self.vulkan.swapchain_present_mode
+        );
+        Ok(())
+    }
+    /// Describe the content to HDR displays, so they can tone map it to their own range
+    fn set_hdr_metadata(
+        hdr_metadata_device: &ash::ext::hdr_metadata::Device,
+        swapchain: ash::vk::SwapchainKHR,
+        surface_format: ash::vk::SurfaceFormatKHR,
+        metadata: ash::vk::HdrMetadataEXT<'static>,
+    ) {
+        let is_hdr = matches!(
+            surface_format.color_space,
+            ash::vk::ColorSpaceKHR::HDR10_ST2084_EXT
+                | ash::vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT
+        );
+        if is_hdr {
+            // Safety: the swapchain was created from the same device as `hdr_metadata_device`
+            unsafe { hdr_metadata_device.set_hdr_metadata(&[swapchain], &[metadata]) };
+        }
+    }
+    /// Replace the swapchain and everything sized to it, e.g. after a resize.
+    ///
+    /// The new swapchain is created from the old one, which is then retired along with its views
+    ///  and framebuffers, so frames still in flight carry on without a wait. Blocks while the
+    ///  window is minimised, as there's nothing to present to.
     fn recreate_swap_chain(&mut self) -> Result<()> {
         let (mut width, mut height) = self.window.get_framebuffer_size();
         while width == 0 && height == 0 {
@@ -1777,47 +2821,88 @@ impl VulkanApp {
             self.glfw.wait_events()
         }
 
-        unsafe { self.vulkan.device.device_wait_idle() }?;
-
-        unsafe {
-            VulkanData::cleanup_swapchain(
-                &self.vulkan.device,
-                &self.vulkan.swapchain_device,
-                std::mem::take(&mut self.vulkan.swap_chain_framebuffers),
-                std::mem::take(&mut self.vulkan.swapchain_image_views),
-                self.vulkan.swapchain,
-            );
-        }
-
+        let swapchain;
         (
-            self.vulkan.swapchain,
+            swapchain,
             self.vulkan.swapchain_images,
-            self.vulkan.swapchain_format,
+            self.vulkan.swapchain_surface_format,
             self.vulkan.swapchain_extent,
+            self.vulkan.swapchain_present_mode,
         ) = unsafe {
             Self::create_swap_chain(
                 &self.window,
                 &self.vulkan.instance,
-                &self.vulkan.surface_instance,
                 &self.vulkan.swapchain_device,
                 self.vulkan.physical_device,
-                self.vulkan.surface,
+                &self.vulkan.surface,
+                self.swapchain_settings,
+                *self.vulkan.swapchain,
             )
         }?;
 
+        // Frames still in flight may be using the old swapchain and everything made for it, so
+        //  retire them rather than waiting for the device to be idle
+        let old_swapchain = std::mem::replace(&mut self.vulkan.swapchain, unsafe {
+            Owned::new(&self.vulkan.device, swapchain)
+        });
+        let deletion_queue = &mut self.vulkan.deletion_queue;
+        if let Some(targets) = self.vulkan.output_pass.take_targets() {
+            deletion_queue.retire(targets);
+        }
+        // Moving to another monitor can change the format, which the output render pass is for
+        let format = self.vulkan.swapchain_surface_format.format;
+        if let Some(old_pipeline) = self.vulkan.output_pass.recreate_for_format(
+            &self.vulkan.device,
+            *self.vulkan.shader_module,
+            format,
+        )? {
+            deletion_queue.retire(old_pipeline);
+            log::info!(
+                target: target::SWAPCHAIN,
+                "Swapchain format changed to {format:?}, recreated the output pipeline"
+            );
+        }
+        deletion_queue.retire(std::mem::take(&mut self.vulkan.swapchain_image_views));
+        deletion_queue.retire(std::mem::take(&mut self.vulkan.render_finished_semaphores));
+        deletion_queue.retire(old_swapchain);
+        log::debug!(
+            target: target::SWAPCHAIN,
+            "Recreated swapchain, {} objects waiting to be destroyed",
+            deletion_queue.len()
+        );
+
         self.vulkan.swapchain_image_views = Self::create_image_views(
             &self.vulkan.device,
             &self.vulkan.swapchain_images,
-            self.vulkan.swapchain_format,
+            self.vulkan.swapchain_surface_format.format,
         )?;
+        let image_count = self.vulkan.swapchain_images.len();
+        self.vulkan.render_finished_semaphores =
+            Self::create_semaphores(&self.vulkan.device, image_count as u32)?;
+        self.vulkan.images_in_flight = vec![None; image_count];
 
-        self.vulkan.swap_chain_framebuffers = Self::create_framebuffers(
+        self.vulkan.output_pass.create_targets(
+            &self.vulkan.instance,
             &self.vulkan.device,
+            self.vulkan.physical_device,
+            *self.vulkan.render_pass,
             &self.vulkan.swapchain_image_views,
-            self.vulkan.render_pass,
             self.vulkan.swapchain_extent,
         )?;
 
+        if let (Some(hdr_metadata_device), Some(hdr_metadata)) =
+            (&self.vulkan.hdr_metadata_device, self.vulkan.hdr_metadata)
+        {
+            Self::set_hdr_metadata(
+                hdr_metadata_device,
+                *self.vulkan.swapchain,
+                self.vulkan.swapchain_surface_format,
+                hdr_metadata,
+            );
+        }
+        self.vulkan.name_swapchain_objects();
+        self.vulkan.add_entry_points();
+
         Ok(())
     }
     fn find_memory_type(
@@ -1839,11 +2924,12 @@ impl VulkanApp {
     }
     fn create_texture_image(
         instance: &ash::Instance,
-        device: &ash::Device,
+        device: &owned::Device,
+        synchronization2_device: Option<&ash::khr::synchronization2::Device>,
         physical_device: ash::vk::PhysicalDevice,
         command_pool: ash::vk::CommandPool,
         graphics_queue: ash::vk::Queue,
-    ) -> Result<(ash::vk::Image, ash::vk::DeviceMemory)> {
+    ) -> Result<(Owned<ash::vk::Image>, Owned<ash::vk::DeviceMemory>)> {
         let path = Path::new("res/texture.png");
         let image = image::open(path)
             .expect("Failed to load texture.png")
@@ -1851,6 +2937,11 @@ impl VulkanApp {
         let height = image.height();
         let width = image.width();
         let pixels: &[_] = &*image;
+        log::info!(
+            target: target::ASSETS,
+            "Loaded texture {} ({width}x{height})",
+            path.display()
+        );
 
         let (staging_buffer, staging_buffer_memory) = Self::create_buffer(
             instance,
@@ -1864,13 +2955,13 @@ impl VulkanApp {
 
         unsafe {
             let data = device.map_memory(
-                staging_buffer_memory,
+                *staging_buffer_memory,
                 0,
                 pixels.len() as _,
                 ash::vk::MemoryMapFlags::empty(),
             )?;
             ptr::copy_nonoverlapping(pixels.as_ptr(), data as _, pixels.len());
-            device.unmap_memory(staging_buffer_memory);
+            device.unmap_memory(*staging_buffer_memory);
         };
 
         let (image, memory) = Self::create_image(
@@ -1887,51 +2978,56 @@ impl VulkanApp {
 
         Self::transition_image_layout(
             device,
+            synchronization2_device,
             command_pool,
             graphics_queue,
-            image,
-            ash::vk::Format::R8G8B8A8_SRGB,
-            ash::vk::ImageLayout::UNDEFINED,
-            ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &ImageTransition::new(
+                *image,
+                ash::vk::Format::R8G8B8A8_SRGB,
+                ash::vk::ImageLayout::UNDEFINED,
+                ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            )
+            .mip_levels(0, ash::vk::REMAINING_MIP_LEVELS)
+            .array_layers(0, ash::vk::REMAINING_ARRAY_LAYERS),
         )?;
 
         Self::copy_buffer_to_image(
             device,
             command_pool,
             graphics_queue,
-            staging_buffer,
-            image,
+            *staging_buffer,
+            *image,
             width,
             height,
         )?;
 
         Self::transition_image_layout(
             device,
+            synchronization2_device,
             command_pool,
             graphics_queue,
-            image,
-            ash::vk::Format::R8G8B8A8_SRGB,
-            ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            &ImageTransition::new(
+                *image,
+                ash::vk::Format::R8G8B8A8_SRGB,
+                ash::vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                ash::vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .mip_levels(0, ash::vk::REMAINING_MIP_LEVELS)
+            .array_layers(0, ash::vk::REMAINING_ARRAY_LAYERS),
         )?;
 
-        unsafe {
-            device.destroy_buffer(staging_buffer, None);
-            device.free_memory(staging_buffer_memory, None);
-        }
-
         Ok((image, memory))
     }
     fn create_texture_image_view(
-        device: &ash::Device,
+        device: &owned::Device,
         texture_image: ash::vk::Image,
-    ) -> Result<ash::vk::ImageView> {
+    ) -> Result<Owned<ash::vk::ImageView>> {
         Self::create_image_view(device, texture_image, ash::vk::Format::R8G8B8A8_SRGB)
     }
     fn create_texture_sampler(
-        device: &ash::Device,
+        device: &owned::Device,
         device_limits: &ash::vk::PhysicalDeviceLimits,
-    ) -> Result<ash::vk::Sampler> {
+    ) -> Result<Owned<ash::vk::Sampler>> {
         let sampler_info = ash::vk::SamplerCreateInfo::default()
             .mag_filter(ash::vk::Filter::LINEAR)
             .min_filter(ash::vk::Filter::LINEAR)
@@ -1950,11 +3046,11 @@ impl VulkanApp {
             .max_lod(0.0);
 
         let sampler = unsafe { device.create_sampler(&sampler_info, None)? };
-        Ok(sampler)
+        Ok(unsafe { Owned::new(device, sampler) })
     }
-    fn create_image(
+    pub(crate) fn create_image(
         instance: &ash::Instance,
-        device: &ash::Device,
+        device: &owned::Device,
         physical_device: ash::vk::PhysicalDevice,
         width: u32,
         height: u32,
@@ -1962,7 +3058,7 @@ impl VulkanApp {
         tiling: ash::vk::ImageTiling,
         usage: ash::vk::ImageUsageFlags,
         properties: ash::vk::MemoryPropertyFlags,
-    ) -> Result<(ash::vk::Image, ash::vk::DeviceMemory)> {
+    ) -> Result<(Owned<ash::vk::Image>, Owned<ash::vk::DeviceMemory>)> {
         let image_info = ash::vk::ImageCreateInfo::default()
             .image_type(ash::vk::ImageType::TYPE_2D)
             .extent(ash::vk::Extent3D {
@@ -1980,9 +3076,9 @@ impl VulkanApp {
             .samples(ash::vk::SampleCountFlags::TYPE_1)
             .flags(ash::vk::ImageCreateFlags::empty());
 
-        let image = unsafe { device.create_image(&image_info, None)? };
+        let image = unsafe { Owned::new(device, device.create_image(&image_info, None)?) };
 
-        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(*image) };
 
         let alloc_info = ash::vk::MemoryAllocateInfo::default()
             .allocation_size(mem_requirements.size)
@@ -1992,8 +3088,8 @@ impl VulkanApp {
                 mem_requirements.memory_type_bits,
                 properties,
             )?);
-        let memory = unsafe { device.allocate_memory(&alloc_info, None)? };
-        unsafe { device.bind_image_memory(image, memory, 0)? };
+        let memory = unsafe { Owned::new(device, device.allocate_memory(&alloc_info, None)?) };
+        unsafe { device.bind_image_memory(*image, *memory, 0)? };
 
         Ok((image, memory))
     }
@@ -2010,141 +3106,174 @@ impl QueueFamilyIndices {
     }
 }
 
+/// Optional device features that were available and have been enabled
+#[derive(Debug)]
+struct EnabledFeatures {
+    synchronization2: bool,
+    draw_indirect_count: bool,
+    timeline_semaphore: bool,
+    hdr_metadata: bool,
+    pipeline_statistics_query: bool,
+}
+
+/// What was asked for when creating the swapchain, kept to apply again when it's recreated
+#[derive(Debug, Clone, Copy)]
+struct SwapchainSettings {
+    /// The requested present mode, which may fall back to another if unsupported
+    present_mode: PresentMode,
+    /// Prefer an HDR surface format, falling back to SDR if there isn't one
+    hdr: bool,
+}
+
 struct SwapChainSupportDetails {
     capabilities: ash::vk::SurfaceCapabilitiesKHR,
     formats: Vec<ash::vk::SurfaceFormatKHR>,
     present_modes: Vec<ash::vk::PresentModeKHR>,
 }
 
-unsafe extern "system" fn debug_callback(
-    message_severity: ash::vk::DebugUtilsMessageSeverityFlagsEXT,
-    message_types: ash::vk::DebugUtilsMessageTypeFlagsEXT,
-    p_callback_data: *const ash::vk::DebugUtilsMessengerCallbackDataEXT<'_>,
-    p_user_data: *mut ffi::c_void,
-) -> ash::vk::Bool32 {
-    let callback_data = p_callback_data.read();
-    let callback = (p_user_data as *mut DebugCallback).read();
-    callback(message_severity, message_types, callback_data);
-
-    ash::vk::FALSE
-}
-
-impl VulkanApp {
-    pub fn cleanup(self) {
-        self.vulkan.cleanup();
+impl Drop for VulkanData {
+    fn drop(&mut self) {
+        // Nothing can still be in use when the fields are dropped and destroyed
+        unsafe { _ = self.device.device_wait_idle() };
     }
 }
 impl VulkanData {
-    unsafe fn cleanup_swapchain(
-        device: &ash::Device,
-        swapchain_device: &ash::khr::swapchain::Device,
-        framebuffers: Vec<ash::vk::Framebuffer>,
-        image_views: Vec<ash::vk::ImageView>,
-        swapchain: ash::vk::SwapchainKHR,
-    ) {
-        unsafe {
-            for framebuffer in framebuffers {
-                device.destroy_framebuffer(framebuffer, None);
-            }
-            for image_view in image_views {
-                device.destroy_image_view(image_view, None);
-            }
-
-            swapchain_device.destroy_swapchain(swapchain, None);
-        }
-    }
-    fn cleanup(self) {
-        unsafe {
-            for sem in self.image_available_semaphores {
-                self.device.destroy_semaphore(sem, None);
-            }
-            for sem in self.render_finished_semaphores {
-                self.device.destroy_semaphore(sem, None);
-            }
-            for fence in self.in_flight_fences {
-                self.device.destroy_fence(fence, None);
-            }
-        }
-
-        unsafe {
-            self.device.destroy_command_pool(self.command_pool, None);
-            self.device.destroy_pipeline(self.graphics_pipeline, None);
-            self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
-            self.device.destroy_shader_module(self.shader_module, None);
-        }
-
-        unsafe {
-            Self::cleanup_swapchain(
-                &self.device,
-                &self.swapchain_device,
-                self.swap_chain_framebuffers,
-                self.swapchain_image_views,
-                self.swapchain,
-            )
-        };
-
-        unsafe {
-            self.device.destroy_sampler(self.texture_sampler, None);
-            self.device
-                .destroy_image_view(self.texture_image_view, None);
-            self.device.destroy_image(self.texture_image, None);
-            self.device.free_memory(self.texture_image_memory, None);
+    /// Name the objects which live as long as the device
+    fn name_objects(&self) {
+        let names = &self.debug_utils;
+        names.set_name(self.device.handle(), "device");
+        names.set_name(self.graphics_queue, "graphics queue");
+        if self.present_queue != self.graphics_queue {
+            names.set_name(self.present_queue, "present queue");
         }
 
-        unsafe {
-            for (buf, mem) in self
-                .uniform_buffers
-                .into_iter()
-                .zip(self.uniform_buffers_memory)
-            {
-                self.device.destroy_buffer(buf, None);
-                self.device.free_memory(mem, None);
-            }
-
-            self.device
-                .destroy_descriptor_pool(self.descriptor_pool, None);
-
-            self.device
-                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
-        }
-
-        unsafe {
-            self.device.destroy_buffer(self.index_buffer, None);
-            self.device.free_memory(self.index_buffer_memory, None);
-            self.device.destroy_buffer(self.vertex_buffer, None);
-            self.device.free_memory(self.vertex_buffer_memory, None);
-        }
-
-        unsafe {
-            self.device.destroy_device(None);
-            _ = self.graphics_queue;
-            _ = self.present_queue;
-            _ = self.device;
-        }
-
-        unsafe {
-            let surface_khr_instance =
-                ash::khr::surface::Instance::new(&self.entry, &self.instance);
-            surface_khr_instance.destroy_surface(self.surface, None);
-        }
+        names.set_name(*self.shader_module, "shaders");
+        names.set_name(*self.render_pass, "scene render pass");
+        names.set_name(*self.descriptor_set_layout, "scene descriptor set layout");
+        names.set_name(*self.pipeline_layout, "scene pipeline layout");
+        names.set_name(*self.graphics_pipeline, "scene pipeline");
+        names.set_name(*self.command_pool, "graphics command pool");
+        names.set_names(&self.command_buffers, "frame command buffer");
+
+        names.set_name(*self.texture_image, "texture");
+        names.set_name(*self.texture_image_memory, "texture memory");
+        names.set_name(*self.texture_image_view, "texture view");
+        names.set_name(*self.texture_sampler, "texture sampler");
+        names.set_name(*self.bindless.descriptor_pool, "bindless descriptor pool");
+        names.set_name(self.bindless.descriptor_set, "bindless descriptor set");
+        names.set_name(
+            *self.bindless.descriptor_set_layout,
+            "bindless descriptor set layout",
+        );
+        names.set_name(*self.vertex_buffer, "vertex buffer");
+        names.set_name(*self.vertex_buffer_memory, "vertex buffer memory");
+        names.set_name(*self.index_buffer, "index buffer");
+        names.set_name(*self.index_buffer_memory, "index buffer memory");
+        names.set_names(&owned::handles(&self.uniform_buffers), "uniform buffer");
+        names.set_names(
+            &owned::handles(&self.uniform_buffers_memory),
+            "uniform buffer memory",
+        );
+        names.set_names(&owned::handles(&self.object_buffers), "object buffer");
+        names.set_names(
+            &owned::handles(&self.object_buffers_memory),
+            "object buffer memory",
+        );
+        names.set_names(&owned::handles(&self.instance_buffers), "instance buffer");
+        names.set_names(
+            &owned::handles(&self.instance_buffers_memory),
+            "instance buffer memory",
+        );
 
-        if let Some(x) = self.debug_callback {
-            if let Some(y) = &self.debug_utils_instance {
-                unsafe { y.destroy_debug_utils_messenger(x, None) };
-            } else {
-                eprintln!("Debug utils instance is lost before cleaning up debug callback");
-            }
-        }
+        names.set_names(
+            &owned::handles(&self.image_available_semaphores),
+            "image available",
+        );
+        self.frame_sync.name(names);
+
+        let culling = &self.gpu_culling;
+        names.set_name(*culling.descriptor_set_layout, "cull descriptor set layout");
+        names.set_name(*culling.pipeline_layout, "cull pipeline layout");
+        names.set_name(*culling.pipeline, "cull pipeline");
+        names.set_name(*culling.descriptor_pool, "cull descriptor pool");
+        names.set_names(&culling.descriptor_sets, "cull descriptor set");
+        names.set_names(
+            &owned::handles(&culling.cull_object_buffers),
+            "cull object buffer",
+        );
+        names.set_names(
+            &owned::handles(&culling.draw_command_buffers),
+            "draw command buffer",
+        );
+        names.set_names(
+            &owned::handles(&culling.draw_count_buffers),
+            "draw count buffer",
+        );
+        names.set_names(
+            &owned::handles(&culling.draw_object_index_buffers),
+            "draw object index buffer",
+        );
 
-        if let Some(y) = self.debug_utils_instance {
-            // No cleanup needed
-            _ = y;
-        }
+        let output = &self.output_pass;
+        names.set_name(
+            *output.descriptor_set_layout,
+            "output descriptor set layout",
+        );
+        names.set_name(*output.sampler, "output sampler");
+    }
+    /// Tell the debug messenger which entry points each pipeline runs, to label printf output
+    fn add_entry_points(&self) {
+        let Some(debug_messenger) = self.instance.debug_messenger() else {
+            return;
+        };
+        use ash::vk::ShaderStageFlags as Stage;
+        debug_messenger.add_entry_points(
+            *self.graphics_pipeline,
+            &[(Stage::VERTEX, "main_vs"), (Stage::FRAGMENT, "main_fs")],
+        );
+        debug_messenger
+            .add_entry_points(*self.gpu_culling.pipeline, &[(Stage::COMPUTE, "cull_cs")]);
+        debug_messenger.add_entry_points(
+            *self.output_pass.pipeline,
+            &[(Stage::VERTEX, "output_vs"), (Stage::FRAGMENT, "output_fs")],
+        );
+    }
+    /// Name the objects which are recreated with the swapchain
+    fn name_swapchain_objects(&self) {
+        let names = &self.debug_utils;
+        names.set_name(*self.swapchain, "swapchain");
+        names.set_names(&self.swapchain_images, "swapchain image");
+        names.set_names(
+            &owned::handles(&self.swapchain_image_views),
+            "swapchain image view",
+        );
+        names.set_names(
+            &owned::handles(&self.render_finished_semaphores),
+            "render finished",
+        );
 
-        unsafe {
-            self.instance.destroy_instance(None);
-        }
+        let output = &self.output_pass;
+        names.set_name(*output.render_pass, "output render pass");
+        names.set_name(*output.pipeline_layout, "output pipeline layout");
+        names.set_name(*output.pipeline, "output pipeline");
+
+        let output = output.targets();
+        names.set_name(*output.descriptor_pool, "output descriptor pool");
+        names.set_name(output.descriptor_set, "output descriptor set");
+        names.set_name(*output.scene_image, "scene colour");
+        names.set_name(*output.scene_image_memory, "scene colour memory");
+        names.set_name(*output.scene_image_view, "scene colour view");
+        names.set_name(*output.scene_framebuffer, "scene framebuffer");
+        names.set_names(&owned::handles(&output.framebuffers), "output framebuffer");
     }
 }
+
+/// A Vulkan version number as `major.minor.patch`
+fn version_string(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        ash::vk::api_version_major(version),
+        ash::vk::api_version_minor(version),
+        ash::vk::api_version_patch(version)
+    )
+}
@@ -0,0 +1,387 @@
+use crate::logging::target;
+use crate::result::{err, Result};
+use glfw::{ClientApiHint, Glfw, GlfwReceiver, Monitor, PWindow, VidMode, WindowEvent, WindowHint};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub(crate) type WindowEvents = GlfwReceiver<(f64, WindowEvent)>;
+
+/// The `[window]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub(crate) struct WindowConfig {
+    pub title: String,
+    pub mode: WindowMode,
+    /// Index into the connected monitors, where 0 is the primary monitor
+    pub monitor: usize,
+    /// Size of the window in windowed mode, in screen coordinates
+    pub width: u32,
+    pub height: u32,
+    /// Video mode for exclusive fullscreen, or the monitor's current mode if not set
+    pub video_mode: Option<VideoModeConfig>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Vulkan".to_string(),
+            mode: WindowMode::Windowed,
+            monitor: 0,
+            width: 800,
+            height: 600,
+            video_mode: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WindowMode {
+    /// A normal decorated window
+    Windowed,
+    /// An undecorated window covering the whole monitor, without changing its video mode
+    Borderless,
+    /// Take over the monitor, switching to the configured video mode
+    Fullscreen,
+}
+
+impl WindowMode {
+    /// The mode to switch to when cycling through them with a hotkey
+    pub fn next(self) -> Self {
+        match self {
+            Self::Windowed => Self::Borderless,
+            Self::Borderless => Self::Fullscreen,
+            Self::Fullscreen => Self::Windowed,
+        }
+    }
+}
+
+/// A requested exclusive fullscreen resolution and refresh rate
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct VideoModeConfig {
+    pub width: u32,
+    pub height: u32,
+    /// In Hz, or the highest the monitor supports at this resolution if not set
+    #[serde(default)]
+    pub refresh_rate: Option<u32>,
+}
+
+/// Where the window was left in windowed mode, restored at the next startup.
+///
+/// This changes every run so lives in its own file next to the config, which isn't checked in.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct WindowState {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl WindowState {
+    const PATH: &str = "res/window_state.toml";
+
+    /// The saved state, if there is any. It's only a convenience, so errors are printed and ignored
+    fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        toml::from_str(&contents)
+            .inspect_err(
+                |e| log::warn!(target: target::WINDOW, "Ignoring invalid {}: {e}", path.display()),
+            )
+            .ok()
+    }
+
+    fn save(&self, path: &Path) {
+        let result = toml::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| std::fs::write(path, contents).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::warn!(target: target::WINDOW, "Failed to save {}: {e}", path.display());
+        }
+    }
+}
+
+/// Switches the window between modes and monitors, remembering its windowed position and size
+/// to return to.
+///
+/// Every change resizes the framebuffer, which recreates the swapchain through the usual
+///  framebuffer resize callback.
+pub(crate) struct WindowManager {
+    mode: WindowMode,
+    monitor: usize,
+    video_mode: Option<VideoModeConfig>,
+    /// The window's position and size while it was last windowed
+    windowed: WindowState,
+    content_scale: (f32, f32),
+}
+
+impl WindowManager {
+    /// Create the window described by `config`, at its saved position and size if there is one
+    pub fn create_window(
+        glfw: &mut Glfw,
+        config: &WindowConfig,
+    ) -> Result<(PWindow, WindowEvents, Self)> {
+        // Disable OpenGL since we want to use Vulkan
+        glfw.window_hint(WindowHint::ClientApi(ClientApiHint::NoApi));
+        glfw.window_hint(WindowHint::Resizable(true));
+        // Size the window in physical pixels proportionally to the monitor's content scale, so
+        //  it appears the same size on high DPI monitors
+        glfw.window_hint(WindowHint::ScaleToMonitor(true));
+
+        let (mut window, events) = glfw
+            .create_window(
+                config.width,
+                config.height,
+                &config.title,
+                glfw::WindowMode::Windowed,
+            )
+            .ok_or(err("Failed to create a window"))?;
+
+        // The saved size was read back from the window, so is already scaled
+        let saved = WindowState::load(Path::new(WindowState::PATH))
+            .filter(|state| Self::is_on_a_monitor(glfw, state));
+        if let Some(state) = saved {
+            window.set_pos(state.x, state.y);
+            window.set_size(state.width as i32, state.height as i32);
+        }
+
+        let mut manager = Self {
+            mode: WindowMode::Windowed,
+            monitor: config.monitor,
+            video_mode: config.video_mode,
+            windowed: WindowState {
+                x: 0,
+                y: 0,
+                width: config.width,
+                height: config.height,
+            },
+            content_scale: window.get_content_scale(),
+        };
+        manager.remember_windowed(&window);
+        if config.mode != WindowMode::Windowed {
+            manager.set_mode(glfw, &mut window, config.mode);
+        }
+
+        // Events read by `handle_event`
+        window.set_pos_polling(true);
+        window.set_size_polling(true);
+        window.set_content_scale_polling(true);
+
+        log::info!(
+            target: target::WINDOW,
+            "Created {:?} window with content scale {:?}",
+            manager.mode, manager.content_scale
+        );
+        Ok((window, events, manager))
+    }
+
+    pub fn mode(&self) -> WindowMode {
+        self.mode
+    }
+
+    /// Track the windowed position and size, and changes to the content scale
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Pos(x, y) if self.mode == WindowMode::Windowed => {
+                (self.windowed.x, self.windowed.y) = (x, y);
+            }
+            WindowEvent::Size(width, height)
+                if self.mode == WindowMode::Windowed && width > 0 && height > 0 =>
+            {
+                (self.windowed.width, self.windowed.height) = (width as u32, height as u32);
+            }
+            WindowEvent::ContentScale(x, y) => {
+                self.content_scale = (x, y);
+                log::debug!(
+                    target: target::WINDOW,
+                    "Content scale changed to {:?}",
+                    self.content_scale
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Switch `window` to `mode` on the current monitor
+    pub fn set_mode(&mut self, glfw: &mut Glfw, window: &mut glfw::Window, mode: WindowMode) {
+        if self.mode == WindowMode::Windowed {
+            self.remember_windowed(window);
+        }
+
+        let monitor = self.monitor;
+        let video_mode = self.video_mode;
+        let windowed = self.windowed;
+        let applied = glfw.with_connected_monitors(|_, monitors| {
+            let Some(monitor) = monitors.get(monitor).or(monitors.first()) else {
+                log::warn!(target: target::WINDOW, "No monitors connected");
+                return false;
+            };
+            Self::apply_mode(window, monitor, mode, video_mode, windowed)
+        });
+
+        if applied {
+            self.mode = mode;
+            log::info!(target: target::WINDOW, "Window mode is now {:?}", self.mode);
+        } else {
+            log::warn!(
+                target: target::WINDOW,
+                "Couldn't switch to {mode:?}, window mode is still {:?}",
+                self.mode
+            );
+        }
+    }
+
+    /// Move the window to the next connected monitor, keeping its mode
+    pub fn next_monitor(&mut self, glfw: &mut Glfw, window: &mut glfw::Window) {
+        if self.mode == WindowMode::Windowed {
+            self.remember_windowed(window);
+        }
+
+        let mode = self.mode;
+        let video_mode = self.video_mode;
+        let current = self.monitor;
+        let mut windowed = self.windowed;
+        // Count and look up the monitors in one go, so one being unplugged can't race us
+        let moved_to = glfw.with_connected_monitors(|_, monitors| {
+            if monitors.is_empty() {
+                return None;
+            }
+            let monitor_index = (current + 1) % monitors.len();
+            let monitor = &monitors[monitor_index];
+            // Centre the window in the new monitor's work area
+            let (x, y, width, height) = monitor.get_workarea();
+            windowed.x = x + (width - windowed.width as i32) / 2;
+            windowed.y = y + (height - windowed.height as i32) / 2;
+            if !Self::apply_mode(window, monitor, mode, video_mode, windowed) {
+                return None;
+            }
+
+            let name = monitor.get_name().unwrap_or_default();
+            log::info!(
+                target: target::WINDOW,
+                "Moved window to monitor {monitor_index} ({name})"
+            );
+            Some(monitor_index)
+        });
+
+        if let Some(monitor_index) = moved_to {
+            self.monitor = monitor_index;
+            self.windowed = windowed;
+        }
+    }
+
+    /// Save the windowed position and size to restore at the next startup
+    pub fn save(&mut self, window: &glfw::Window) {
+        if self.mode == WindowMode::Windowed {
+            self.remember_windowed(window);
+        }
+        self.windowed.save(Path::new(WindowState::PATH));
+    }
+
+    fn remember_windowed(&mut self, window: &glfw::Window) {
+        let (x, y) = window.get_pos();
+        let (width, height) = window.get_size();
+        self.windowed = WindowState {
+            x,
+            y,
+            width: width.max(1) as u32,
+            height: height.max(1) as u32,
+        };
+    }
+
+    /// Put `window` in `mode` on `monitor`, returning whether it could be
+    fn apply_mode(
+        window: &mut glfw::Window,
+        monitor: &Monitor,
+        mode: WindowMode,
+        video_mode: Option<VideoModeConfig>,
+        windowed: WindowState,
+    ) -> bool {
+        match mode {
+            WindowMode::Windowed => {
+                window.set_decorated(true);
+                window.set_monitor(
+                    glfw::WindowMode::Windowed,
+                    windowed.x,
+                    windowed.y,
+                    windowed.width,
+                    windowed.height,
+                    None,
+                );
+            }
+            WindowMode::Borderless => {
+                let Some(current) = monitor.get_video_mode() else {
+                    log::warn!(
+                        target: target::WINDOW,
+                        "Monitor has no video mode, can't go borderless"
+                    );
+                    return false;
+                };
+                let (x, y) = monitor.get_pos();
+                window.set_decorated(false);
+                window.set_monitor(
+                    glfw::WindowMode::Windowed,
+                    x,
+                    y,
+                    current.width,
+                    current.height,
+                    None,
+                );
+            }
+            WindowMode::Fullscreen => {
+                let Some(chosen) = Self::choose_video_mode(monitor, video_mode) else {
+                    log::warn!(
+                        target: target::WINDOW,
+                        "Monitor has no video modes, can't go fullscreen"
+                    );
+                    return false;
+                };
+                window.set_monitor(
+                    glfw::WindowMode::FullScreen(monitor),
+                    0,
+                    0,
+                    chosen.width,
+                    chosen.height,
+                    Some(chosen.refresh_rate),
+                );
+            }
+        }
+        true
+    }
+
+    /// The monitor's video mode closest to `requested`, or its current mode if nothing was requested
+    fn choose_video_mode(monitor: &Monitor, requested: Option<VideoModeConfig>) -> Option<VidMode> {
+        let current = monitor.get_video_mode();
+        let Some(requested) = requested else {
+            return current;
+        };
+
+        let chosen = monitor
+            .get_video_modes()
+            .into_iter()
+            .filter(|mode| mode.width == requested.width && mode.height == requested.height)
+            .max_by_key(|mode| match requested.refresh_rate {
+                // Prefer the closest refresh rate, otherwise the highest
+                Some(refresh_rate) => -(mode.refresh_rate.abs_diff(refresh_rate) as i64),
+                None => mode.refresh_rate as i64,
+            });
+        if chosen.is_none() {
+            log::warn!(
+                target: target::WINDOW,
+                "Video mode {}x{} isn't supported, using the current mode",
+                requested.width, requested.height
+            );
+        }
+        chosen.or(current)
+    }
+
+    /// Whether the top left of a saved window is still on a connected monitor, so it's visible
+    fn is_on_a_monitor(glfw: &mut Glfw, state: &WindowState) -> bool {
+        glfw.with_connected_monitors(|_, monitors| {
+            monitors.iter().any(|monitor| {
+                let (x, y, width, height) = monitor.get_workarea();
+                (x..x + width).contains(&state.x) && (y..y + height).contains(&state.y)
+            })
+        })
+    }
+}